@@ -1,5 +1,10 @@
 use std::fmt;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use super::token::{Span};
 
 #[derive(Debug,PartialEq)]
 pub struct Program {
@@ -16,29 +21,71 @@ impl fmt::Display for Program {
 
 #[derive(Debug,PartialEq, Clone, Eq, Hash, Ord, PartialOrd)]
 pub enum Statement {
-    LetStatement{identifier: Expression,
-                 value: Expression},
-    Return(Expression),
+    LetStatement{pattern: Pattern,
+                 value: Expression,
+                 span: Span},
+    Return(Expression, Span),
     ExpressionStatement(Expression),
-    Block(Vec<Statement>),
+    Block(Vec<Statement>, Span),
+    While{condition: Expression,
+          body: Box<Statement>,
+          span: Span},
+    // variables are one identifier for values, or two identifiers for indexes (or keys) and values.
+    For{variables: Vec<Expression>,
+        iterable: Expression,
+        body: Box<Statement>,
+        span: Span},
+    Break(Span),
+    Continue(Span),
+    Throw(Expression, Span),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::LetStatement{span, ..} => *span,
+            Statement::Return(_, span) => *span,
+            Statement::ExpressionStatement(expression) => expression.span(),
+            Statement::Block(_, span) => *span,
+            Statement::While{span, ..} => *span,
+            Statement::For{span, ..} => *span,
+            Statement::Break(span) => *span,
+            Statement::Continue(span) => *span,
+            Statement::Throw(_, span) => *span,
+        }
+    }
+
+    // an if expression is the alternative of another one when they are written as "else if".
+    fn is_if_expression(&self) -> bool {
+        matches!(self, Statement::ExpressionStatement(Expression::IfExpression{..}))
+    }
 }
 
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Statement::LetStatement{
-                               identifier,
-                               value
-                                    } =>write!(f, "let {} = {};",identifier,  value),
-            Statement::Return(Expression) =>write!(f, "return {}", Expression),
+                               pattern,
+                               value,
+                               ..
+                                    } =>write!(f, "let {} = {};",pattern,  value),
+            Statement::Return(Expression, _) =>write!(f, "return {}", Expression),
             Statement::ExpressionStatement(Expression) =>write!(f, "{}", Expression),
-            Statement::Block(Statements) => {
+            Statement::Block(Statements, _) => {
                                              for stmt in Statements.iter()
                                                  {
                                                   write!(f, "{}", stmt)?;
                                                  }
                                             Ok(())  
                                             },
+            Statement::While{condition, body, ..} => write!(f, "while ({}) {{{}}}", condition, body),
+            Statement::For{variables, iterable, body, ..} => write!(f, "for ({} in {}) {{{}}}",
+                                                                    variables.iter().map(|variable| format!("{}", variable)).collect::<Vec<_>>().join(", "),
+                                                                    iterable,
+                                                                    body),
+            Statement::Break(_) => write!(f, "break"),
+            Statement::Continue(_) => write!(f, "continue"),
+            Statement::Throw(value, _) => write!(f, "throw {}", value),
            _ => write!(f, "none")
                     }
                 }
@@ -46,64 +93,146 @@ impl fmt::Display for Statement {
 
 #[derive(Debug,PartialEq, Clone, Eq, Hash, Ord, PartialOrd)]
 pub enum Expression {
-    Identifier(String),
-    String(String),
-    Integer(i32),
+    Identifier(String, Span),
+    String(String, Span),
+    // a string with ${...}. parts are strings and expressions in order.
+    Interpolation{parts: Vec<Expression>,
+                  span: Span
+                 },
+    Integer(i32, Span),
+    Float(Float, Span),
     LParen(String),
-    Array(Vec<Expression>),
-    Hashmap(BTreeMap<Box<Expression>, Box<Expression>>),
-    Bool(bool),
+    Array(Vec<Expression>, Span),
+    Hashmap(BTreeMap<Box<Expression>, Box<Expression>>, Span),
+    Bool(bool, Span),
     IndexExpression{array: Box<Expression>,
-                    subscript: Box<Expression>},
+                    subscript: Box<Expression>,
+                    span: Span},
     PrefixExpression{operator: String,
-                     right_expression: Box<Expression>
+                     right_expression: Box<Expression>,
+                     span: Span
                      },
     InfixExpression{left_expression: Box<Expression>,
                     operator: String,
-                    right_expression: Box<Expression>
+                    right_expression: Box<Expression>,
+                    span: Span
                    },
+    // match (value) { pattern => body, pattern if guard => body }. arms are tried in order.
+    Match{value: Box<Expression>,
+          arms: Vec<MatchArm>,
+          span: Span
+         },
     IfExpression{condition: Box<Expression>,
                  consequence: Box<Statement>,
-                 alternative: Option<Box<Statement>>
+                 alternative: Option<Box<Statement>>,
+                 span: Span
                 },
-    FunctionLiteral{parameters: Vec<Expression>,
-                    body: Box<Statement>,
+    // catch is the name of the caught error and the block which handles it.
+    Try{body: Box<Statement>,
+        catch: Option<(String, Box<Statement>)>,
+        finally: Option<Box<Statement>>,
+        span: Span
+       },
+    // parameters and body are shared with the functions which are made from the literal.
+    // name is given only by the declaration, fn name(params) { body }.
+    FunctionLiteral{name: Option<String>,
+                    parameters: Rc<Vec<Parameter>>,
+                    body: Rc<Statement>,
+                    span: Span
                    },
     CallExpression{function: Box<Expression>,
-                    body: Vec<Expression>
+                    body: Vec<Expression>,
+                    span: Span
                   },
+    // ...array in a call or an array literal, whose elements are put there one by one.
+    Spread(Box<Expression>, Span),
+    // name: value in a call, which is given to the parameter of the name.
+    Keyword{name: String,
+            value: Box<Expression>,
+            span: Span
+           },
+    // target is an identifier or an index expression, and operator is "=" or a compound one such as "+=".
+    Assign{target: Box<Expression>,
+           operator: String,
+           value: Box<Expression>,
+           span: Span
+          },
     Null
 }
 
+impl Expression {
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Identifier(_, span) => *span,
+            Expression::String(_, span) => *span,
+            Expression::Interpolation{span, ..} => *span,
+            Expression::Integer(_, span) => *span,
+            Expression::Float(_, span) => *span,
+            Expression::Array(_, span) => *span,
+            Expression::Hashmap(_, span) => *span,
+            Expression::Bool(_, span) => *span,
+            Expression::IndexExpression{span, ..} => *span,
+            Expression::PrefixExpression{span, ..} => *span,
+            Expression::InfixExpression{span, ..} => *span,
+            Expression::IfExpression{span, ..} => *span,
+            Expression::Match{span, ..} => *span,
+            Expression::Try{span, ..} => *span,
+            Expression::FunctionLiteral{span, ..} => *span,
+            Expression::CallExpression{span, ..} => *span,
+            Expression::Spread(_, span) => *span,
+            Expression::Keyword{span, ..} => *span,
+            Expression::Assign{span, ..} => *span,
+            Expression::LParen(_) => Span::default(),
+            Expression::Null => Span::default(),
+        }
+    }
+}
+
 impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Expression::Identifier(value) => write!(f, "{}",&value),
-            Expression::String(value) => write!(f, "{}", &value),
-            Expression::Integer(value) => write!(f, "{}",value),
+            Expression::Identifier(value, _) => write!(f, "{}",&value),
+            Expression::String(value, _) => write!(f, "{}", &value),
+            Expression::Interpolation{parts, ..} => write!(f, "{}", parts.iter().map(|part| match part {
+                                                                                    Expression::String(value, _) => value.to_string(),
+                                                                                    _ => format!("${{{}}}", part),
+                                                                                }).collect::<Vec<_>>().join("")),
+            Expression::Integer(value, _) => write!(f, "{}",value),
+            Expression::Float(value, _) => write!(f, "{}",value),
             Expression::LParen(value) => write!(f, "{}",value),
-            Expression::Bool(value) => write!(f, "{}",value),
-            Expression::PrefixExpression{operator,right_expression} => write!(f, "{}{}",operator, right_expression),
-            Expression::InfixExpression{left_expression,operator,right_expression} => write!(f, "{} {} {}",left_expression, operator, right_expression),
-            Expression::IfExpression{condition, consequence, alternative} => {
+            Expression::Bool(value, _) => write!(f, "{}",value),
+            Expression::PrefixExpression{operator,right_expression, ..} => write!(f, "{}{}",operator, right_expression),
+            Expression::InfixExpression{left_expression,operator,right_expression, ..} => write!(f, "{} {} {}",left_expression, operator, right_expression),
+            Expression::IfExpression{condition, consequence, alternative, ..} => {
                                                     match alternative {
+                                                        Some(alternative) if alternative.is_if_expression() => write!(f, "if ({}) {{{}}} else {}",condition, consequence, alternative),
                                                         Some(alternative) =>write!(f, "if ({}) {{{}}} else {{{}}}",condition, consequence, alternative),
                                                         None => write!(f, "if ({}) {{{}}}",condition, consequence),
                                                     }
                                                     }//write!(f, "if ({}) {{{}}} else {{{}}}",condition, consequence, alternative),
-            Expression::FunctionLiteral{parameters, body} => write!(f, "fn ({}) {{{}}}",parameters.iter().map(|expression| -> &str {
-                                                                                                                        match expression {
-                                                                                                                            Expression::Identifier(identifier) => identifier,
-                                                                                                                            _ => unreachable!(),
-                                                                                                                        }}).collect::<Vec<_>>().join(", ")
+            Expression::Match{value, arms, ..} => write!(f, "match ({}) {{{}}}", value, arms.iter().map(|arm| format!("{}", arm)).collect::<Vec<_>>().join(", ")),
+            Expression::Try{body, catch, finally, ..} => {
+                write!(f, "try {{{}}}", body)?;
+                if let Some((name, handler)) = catch {
+                    write!(f, " catch ({}) {{{}}}", name, handler)?;
+                }
+                if let Some(finally) = finally {
+                    write!(f, " finally {{{}}}", finally)?;
+                }
+                Ok(())
+            },
+            Expression::FunctionLiteral{name, parameters, body, ..} => write!(f, "fn {}({}) {{{}}}", name.as_deref().unwrap_or(""), parameters.iter().map(|parameter| format!("{}", parameter)).collect::<Vec<_>>().join(", ")
                                                                                                                       , body),
-            Expression::CallExpression{function, body} => write!(f, "{}({});",
+            Expression::CallExpression{function, body, ..} => write!(f, "{}({});",
                                                                 function,
                                                                 body.iter().map(|expression| format!("{}", &expression)).collect::<Vec<_>>().join(", "),
                                                                 ),
-            Expression::Array(value) => write!(f, "[{}]", value.iter().map(|expression| format!("{}", &expression)).collect::<Vec<_>>().join(", ")),
-            Expression::IndexExpression{array, subscript} => write!(f, "{}[{}]",array, subscript),
-            Expression::Hashmap(tree) => {
+            Expression::Array(value, _) => write!(f, "[{}]", value.iter().map(|expression| format!("{}", &expression)).collect::<Vec<_>>().join(", ")),
+            Expression::IndexExpression{array, subscript, ..} => write!(f, "{}[{}]",array, subscript),
+            Expression::Spread(value, _) => write!(f, "...{}", value),
+            Expression::Keyword{name, value, ..} => write!(f, "{}: {}", name, value),
+            Expression::Assign{target, operator, value, ..} => write!(f, "{} {} {}", target, operator, value),
+            Expression::Hashmap(tree, _) => {
                 match tree {
                     tree => write!(f, "{{{}}}", tree.iter().map(|(key, value)| format!("{}: {}", key, value)).collect::<Vec<_>>().join(", ")),
                     _ =>  unreachable!()}
@@ -114,14 +243,153 @@ impl fmt::Display for Expression {
 }
 
 
+// a parameter of a function. it can have a default value like y = 2,
+// or take the rest of arguments as an array like ...rest.
+#[derive(Debug,PartialEq, Clone, Eq, Hash, Ord, PartialOrd)]
+pub struct Parameter {
+    pub name: String,
+    pub default: Option<Expression>,
+    pub rest: bool,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.default {
+            _ if self.rest => write!(f, "...{}", self.name),
+            Some(default) => write!(f, "{} = {}", self.name, default),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+// the left side of let or an arm of match, which binds names to the parts of a value.
+#[derive(Debug,PartialEq, Clone, Eq, Hash, Ord, PartialOrd)]
+pub enum Pattern {
+    Identifier(String, Span),
+    // _ matches any value without binding it.
+    Wildcard(Span),
+    // a number, a string or a boolean, which matches an equal value.
+    Literal(Expression),
+    // [a, b, ...rest]. rest takes the elements after the others as an array.
+    Array{elements: Vec<Pattern>,
+          rest: Option<String>,
+          span: Span
+         },
+    // {"name": n, "age": a}. keys are expressions, and only the keys written here are taken.
+    Hash{pairs: Vec<(Expression, Pattern)>,
+         span: Span
+        },
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Identifier(_, span) => *span,
+            Pattern::Wildcard(span) => *span,
+            Pattern::Literal(value) => value.span(),
+            Pattern::Array{span, ..} => *span,
+            Pattern::Hash{span, ..} => *span,
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Identifier(name, _) => write!(f, "{}", name),
+            Pattern::Wildcard(_) => write!(f, "_"),
+            Pattern::Literal(value) => write!(f, "{}", value),
+            Pattern::Array{elements, rest, ..} => write!(f, "[{}]", elements.iter().map(|element| format!("{}", element))
+                                                                            .chain(rest.iter().map(|rest| format!("...{}", rest)))
+                                                                            .collect::<Vec<_>>().join(", ")),
+            Pattern::Hash{pairs, ..} => write!(f, "{{{}}}", pairs.iter().map(|(key, value)| format!("{}: {}", key, value)).collect::<Vec<_>>().join(", ")),
+        }
+    }
+}
+
+// an arm of match. the body is evaluated if the value matches the pattern and the guard is true.
+#[derive(Debug,PartialEq, Clone, Eq, Hash, Ord, PartialOrd)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Expression,
+}
+
+impl fmt::Display for MatchArm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.guard {
+            Some(guard) => write!(f, "{} if {} => {}", self.pattern, guard, self.body),
+            None => write!(f, "{} => {}", self.pattern, self.body),
+        }
+    }
+}
+
+// f64 which can be a part of nodes and objects.
+// it is compared by its bits, so that nodes can be keys of maps.
+// the order is the totalOrder of IEEE 754, which agrees with the equality. for example, -0.0 is smaller than 0.0.
+// this is the identity of the value, not the == of the language: Float(NaN) == Float(NaN) and Float(0.0) != Float(-0.0).
+// so values which scripts compare, such as the literals of patterns, must go through the == operator of the evaluator.
+#[derive(Debug, Clone, Copy)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Float) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for Float {}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Float) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Float {
+    fn cmp(&self, other: &Float) -> Ordering {
+        // the bits of a negative number are flipped except the sign, so that they are ordered as integers.
+        let key = |value: f64| {
+            let bits = value.to_bits() as i64;
+            bits ^ (((bits >> 63) as u64) >> 1) as i64
+        };
+        key(self.0).cmp(&key(other.0))
+    }
+}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl fmt::Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // whole numbers are shown with ".0", so that they look different from integers.
+        if self.0.is_finite() && self.0.fract() == 0.0 && self.0.abs() < 1e16 {
+            write!(f, "{:.1}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, PartialOrd)]
 pub enum Precedence {
     LOWEST,      
+    ASSIGN,       // = or +=
+    LOGICALOR,    // ||
+    LOGICALAND,   // &&
     EQUALS,       // ==
     LESSGREATER,  // > or <
+    BITOR,        // |
+    BITXOR,       // ^
+    BITAND,       // &
+    SHIFT,        // << or >>
     SUM,          // +
     PRODUCT,      // *
     PREFIX,       // -X or !X
+    EXPONENT,     // **
     CALL,         // my_cunction(x){}
     LBRACKET,     // []
 }
@@ -1,84 +1,185 @@
+extern crate unicode_segmentation;
+
+use unicode_segmentation::UnicodeSegmentation;
+
 use super::object::{Object};
-use super::errors::{Errors};
-use std::collections::BTreeMap;
-
-pub fn new() -> BTreeMap<String, Object> {
-    let mut builtins = BTreeMap::new();
-    builtins.insert(String::from("len"), Object::Builtin{func: len});
-    builtins.insert(String::from("first"), Object::Builtin{func: first});
-    builtins.insert(String::from("last"), Object::Builtin{func: last});
-    builtins.insert(String::from("rest"), Object::Builtin{func: rest}); 
-    builtins.insert(String::from("push"), Object::Builtin{func: push}); 
-    builtins.insert(String::from("print"), Object::Builtin{func: print});
-    builtins
+use super::ast::{Float};
+use super::errors::{Errors, ErrorKind};
+
+// a builtin returns an error in the same way as the evaluator, and it gets the position of the call later.
+pub type BuiltinFunction = fn(Vec<Object>) -> Result<Object, Errors>;
+
+// every builtin function. the table is shared by all environments, so it is never copied.
+// a builtin which names its parameters here can be called with keyword arguments.
+static BUILTINS: &[(&str, &[&str], BuiltinFunction)] = &[
+    ("len", &[], len),
+    ("first", &[], first),
+    ("last", &[], last),
+    ("rest", &[], rest),
+    ("push", &["array", "value"], push),
+    ("print", &[], print),
+    ("graphemes", &[], graphemes),
+    ("sqrt", &[], sqrt),
+    ("floor", &[], floor),
+    ("round", &[], round),
+    ("pow", &["base", "exponent"], pow),
+];
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|(name, _, _)| *name)
+}
+
+pub fn get(name: &str) -> Option<Object> {
+    BUILTINS.iter()
+            .find(|(builtin, _, _)| *builtin == name)
+            .map(|(_, params, func)| Object::Builtin{func: *func, params: *params})
 }
 
-fn len(args: Vec<Object>) -> Object {
+fn len(args: Vec<Object>) -> Result<Object, Errors> {
     if args.len() != 1 {
-        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+        return Err(ErrorKind::InvalidNumberOfArguments{got: args.len(), want:1}.into());
     }
 
     match &args[0] {
         Object::Array(value) => {
-            Object::Integer(value.len() as i32)
+            Ok(Object::Integer(value.len() as i32))
         }
-        Object::String(string) => Object::Integer(string.len() as i32),
+        // the length of a string is the number of characters, not bytes.
+        Object::String(string) => Ok(Object::Integer(string.chars().count() as i32)),
         _ => {
-            Object::Error(Errors::LenInvalidTypeError(Box::new(args[0].clone())))
+            Err(ErrorKind::LenInvalidTypeError(Box::new(args[0].clone())).into())
             },
     }
 }
 
-fn first(args: Vec<Object>) -> Object {
+// first, last and rest give null for an empty array.
+fn first(args: Vec<Object>) -> Result<Object, Errors> {
+    if args.len() != 1 {
+        return Err(ErrorKind::InvalidNumberOfArguments{got: args.len(), want:1}.into());
+    }
+
     match &args[0] {
         Object::Array(value) => {
-            value[0].clone()
+            Ok(value.first().cloned().unwrap_or(Object::Null))
         },
-        _ =>  Object::Error(Errors::FirstTypeError(Box::new(args[0].clone())))
+        _ =>  Err(ErrorKind::FirstTypeError(Box::new(args[0].clone())).into())
     }
 }
 
-fn last(args: Vec<Object>) -> Object {
+fn last(args: Vec<Object>) -> Result<Object, Errors> {
+    if args.len() != 1 {
+        return Err(ErrorKind::InvalidNumberOfArguments{got: args.len(), want:1}.into());
+    }
+
     match &args[0] {
         Object::Array(value) => {
-            let length = value.len();
-            value[length-1].clone()
+            Ok(value.last().cloned().unwrap_or(Object::Null))
         },
-        _ =>  Object::Error(Errors::LastTypeError(Box::new(args[0].clone())))
+        _ =>  Err(ErrorKind::LastTypeError(Box::new(args[0].clone())).into())
     }
 }
 
-fn rest(args: Vec<Object>) -> Object {
+// the array without its last value.
+fn rest(args: Vec<Object>) -> Result<Object, Errors> {
+    if args.len() != 1 {
+        return Err(ErrorKind::InvalidNumberOfArguments{got: args.len(), want:1}.into());
+    }
+
     match &args[0] {
-        Object::Array(value) => {
-            let length = value.len();
-            let mut array: Vec<Object> = Vec::new();
-            for (i, v) in value.iter().enumerate(){
-                array.push(v.clone());
-                if i == length -2 {
-                    return Object::Array(array)
-                }
-            }
-            value[length-1].clone()
+        Object::Array(value) => match value.split_last() {
+            Some((_, init)) => Ok(Object::Array(init.to_vec())),
+            None => Ok(Object::Null)
         },
-        _ =>  Object::Error(Errors::RestTypeError(Box::new(args[0].clone())))
+        _ =>  Err(ErrorKind::RestTypeError(Box::new(args[0].clone())).into())
     }
 }
 
-fn push(args: Vec<Object>) -> Object {
+fn push(args: Vec<Object>) -> Result<Object, Errors> {
+    if args.len() != 2 {
+        return Err(ErrorKind::InvalidNumberOfArguments{got: args.len(), want:2}.into());
+    }
+
     match &args[0] {
         Object::Array(value) => {
             let mut array = value.clone();
             array.push(args[1].clone());
-            Object::Array(array.clone())
+            Ok(Object::Array(array.clone()))
         },
-        _ =>  Object::Error(Errors::PushTypeError(Box::new(args[0].clone())))
+        _ =>  Err(ErrorKind::PushTypeError(Box::new(args[0].clone())).into())
     }
 }
 
-fn print(args: Vec<Object>) -> Object {
+fn print(args: Vec<Object>) -> Result<Object, Errors> {
     for arg in args.iter() {
         eprint!("{}", arg);
     }
-    Object::Null
-}
\ No newline at end of file
+    Ok(Object::Null)
+}
+
+// split a string into characters as people see them,
+// for example "が" which is written with a combining mark is one grapheme.
+fn graphemes(args: Vec<Object>) -> Result<Object, Errors> {
+    if args.len() != 1 {
+        return Err(ErrorKind::InvalidNumberOfArguments{got: args.len(), want:1}.into());
+    }
+
+    match &args[0] {
+        Object::String(string) => {
+            Ok(Object::Array(string.graphemes(true).map(|grapheme| Object::String(grapheme.to_string())).collect()))
+        },
+        _ =>  Err(ErrorKind::GraphemesTypeError(Box::new(args[0].clone())).into())
+    }
+}
+
+// the value of an integer or a float as f64, for math functions.
+fn to_float(name: &str, arg: &Object) -> Result<f64, Errors> {
+    match arg {
+        Object::Integer(value) => Ok(*value as f64),
+        Object::Float(Float(value)) => Ok(*value),
+        _ => Err(ErrorKind::NumberTypeError{function: name.to_string(), got: Box::new(arg.clone())}.into())
+    }
+}
+
+// the integer which is rounded by floor() or round().
+fn to_integer(value: f64) -> Result<Object, Errors> {
+    if !value.is_finite() {
+        Err(ErrorKind::NotFinite(Float(value)).into())
+    } else if value < i32::MIN as f64 || value > i32::MAX as f64 {
+        Err(ErrorKind::IntegerOverflow.into())
+    } else {
+        Ok(Object::Integer(value as i32))
+    }
+}
+
+fn sqrt(args: Vec<Object>) -> Result<Object, Errors> {
+    if args.len() != 1 {
+        return Err(ErrorKind::InvalidNumberOfArguments{got: args.len(), want:1}.into());
+    }
+    let value = to_float("sqrt", &args[0])?;
+    Ok(Object::Float(Float(value.sqrt())))
+}
+
+fn floor(args: Vec<Object>) -> Result<Object, Errors> {
+    if args.len() != 1 {
+        return Err(ErrorKind::InvalidNumberOfArguments{got: args.len(), want:1}.into());
+    }
+    let value = to_float("floor", &args[0])?;
+    to_integer(value.floor())
+}
+
+fn round(args: Vec<Object>) -> Result<Object, Errors> {
+    if args.len() != 1 {
+        return Err(ErrorKind::InvalidNumberOfArguments{got: args.len(), want:1}.into());
+    }
+    let value = to_float("round", &args[0])?;
+    to_integer(value.round())
+}
+
+fn pow(args: Vec<Object>) -> Result<Object, Errors> {
+    if args.len() != 2 {
+        return Err(ErrorKind::InvalidNumberOfArguments{got: args.len(), want:2}.into());
+    }
+    let base = to_float("pow", &args[0])?;
+    let exponent = to_float("pow", &args[1])?;
+    Ok(Object::Float(Float(base.powf(exponent))))
+}
@@ -1,11 +1,22 @@
 use std::fmt;
+use std::ops::{Deref, DerefMut};
 
-use super::token::{Token};
+use super::token::{Token, TokenKind, Span};
 use super::object::{Object};
+use super::ast::{Float};
 
 #[derive(Debug, PartialEq, Clone, Eq, Ord, PartialOrd)]
-pub enum Errors {
+pub enum ErrorKind {
+    IllegalCharacter(char),
+    UnterminatedString,
+    UnterminatedComment,
+    InvalidEscape(String),
+    IntegerOutOfRange(String),
+    InvalidNumberLiteral(String),
     TokenInvalid(Token),
+    UnexpectedToken{want: TokenKind,
+                    got: Token
+                   },
     InvalidOperator(String),
     InvalidInteger(Box<Object>),
     InvalidIdentifier(Box<Object>),
@@ -19,23 +30,272 @@ pub enum Errors {
     LastTypeError(Box<Object>),
     RestTypeError(Box<Object>),
     PushTypeError(Box<Object>),
+    GraphemesTypeError(Box<Object>),
+    NumberTypeError{function: String,
+                    got: Box<Object>
+                   },
+    IntegerOverflow,
+    NotFinite(Float),
+    DivisionByZero,
+    ShiftOutOfRange(i32),
+    OutsideLoop(String),
+    NotIterable(Box<Object>),
+    InvalidAssignTarget(String),
+    UndeclaredVariable(String),
+    IndexOutOfRange(i32),
+    UnusableHashKey(Box<Object>),
+    IndexAssignTypeError(Box<Object>),
+    IndexTypeError{container: Box<Object>,
+                   index: Box<Object>
+                  },
+    RestParameterNotLast(String),
+    SpreadTypeError(Box<Object>),
+    PositionalAfterKeyword,
+    UnknownKeywordArgument(String),
+    DuplicateArgument(String),
+    DestructureTypeError{pattern: String,
+                         got: Box<Object>
+                        },
+    DestructureLengthMismatch{want: usize,
+                              got: usize,
+                              rest: bool
+                             },
+    MissingHashKey(Box<Object>),
+    PatternMismatch{pattern: String,
+                    got: Box<Object>
+                   },
+    NoMatchingArm(Box<Object>),
+    // suggestions are the names which are defined and close to name.
+    UndefinedIdentifier{name: String,
+                        suggestions: Vec<String>
+                       },
+    NotCallable(Box<Object>),
+    TryWithoutHandler,
+    CallDepthExceeded(usize),
+    NestingTooDeep(usize),
+    // the value of a throw statement.
+    Thrown(Box<Object>),
 }
 
-impl fmt::Display for Errors {
+impl ErrorKind {
+    // the name of the kind such as DivisionByZero, which scripts read from a caught error.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::IllegalCharacter(..) => "IllegalCharacter",
+            ErrorKind::UnterminatedString => "UnterminatedString",
+            ErrorKind::UnterminatedComment => "UnterminatedComment",
+            ErrorKind::InvalidEscape(..) => "InvalidEscape",
+            ErrorKind::IntegerOutOfRange(..) => "IntegerOutOfRange",
+            ErrorKind::InvalidNumberLiteral(..) => "InvalidNumberLiteral",
+            ErrorKind::TokenInvalid(..) => "TokenInvalid",
+            ErrorKind::UnexpectedToken{..} => "UnexpectedToken",
+            ErrorKind::InvalidOperator(..) => "InvalidOperator",
+            ErrorKind::InvalidInteger(..) => "InvalidInteger",
+            ErrorKind::InvalidIdentifier(..) => "InvalidIdentifier",
+            ErrorKind::InvalidInfix => "InvalidInfix",
+            ErrorKind::NodeError => "NodeError",
+            ErrorKind::InvalidNumberOfArguments{..} => "InvalidNumberOfArguments",
+            ErrorKind::LenInvalidTypeError(..) => "LenInvalidTypeError",
+            ErrorKind::FirstTypeError(..) => "FirstTypeError",
+            ErrorKind::LastTypeError(..) => "LastTypeError",
+            ErrorKind::RestTypeError(..) => "RestTypeError",
+            ErrorKind::PushTypeError(..) => "PushTypeError",
+            ErrorKind::GraphemesTypeError(..) => "GraphemesTypeError",
+            ErrorKind::NumberTypeError{..} => "NumberTypeError",
+            ErrorKind::IntegerOverflow => "IntegerOverflow",
+            ErrorKind::NotFinite(..) => "NotFinite",
+            ErrorKind::DivisionByZero => "DivisionByZero",
+            ErrorKind::ShiftOutOfRange(..) => "ShiftOutOfRange",
+            ErrorKind::OutsideLoop(..) => "OutsideLoop",
+            ErrorKind::NotIterable(..) => "NotIterable",
+            ErrorKind::InvalidAssignTarget(..) => "InvalidAssignTarget",
+            ErrorKind::UndeclaredVariable(..) => "UndeclaredVariable",
+            ErrorKind::IndexOutOfRange(..) => "IndexOutOfRange",
+            ErrorKind::UnusableHashKey(..) => "UnusableHashKey",
+            ErrorKind::IndexAssignTypeError(..) => "IndexAssignTypeError",
+            ErrorKind::IndexTypeError{..} => "IndexTypeError",
+            ErrorKind::RestParameterNotLast(..) => "RestParameterNotLast",
+            ErrorKind::SpreadTypeError(..) => "SpreadTypeError",
+            ErrorKind::PositionalAfterKeyword => "PositionalAfterKeyword",
+            ErrorKind::UnknownKeywordArgument(..) => "UnknownKeywordArgument",
+            ErrorKind::DuplicateArgument(..) => "DuplicateArgument",
+            ErrorKind::DestructureTypeError{..} => "DestructureTypeError",
+            ErrorKind::DestructureLengthMismatch{..} => "DestructureLengthMismatch",
+            ErrorKind::MissingHashKey(..) => "MissingHashKey",
+            ErrorKind::PatternMismatch{..} => "PatternMismatch",
+            ErrorKind::NoMatchingArm(..) => "NoMatchingArm",
+            ErrorKind::UndefinedIdentifier{..} => "UndefinedIdentifier",
+            ErrorKind::NotCallable(..) => "NotCallable",
+            ErrorKind::TryWithoutHandler => "TryWithoutHandler",
+            ErrorKind::CallDepthExceeded(..) => "CallDepthExceeded",
+            ErrorKind::NestingTooDeep(..) => "NestingTooDeep",
+            ErrorKind::Thrown(..) => "Thrown",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Errors::TokenInvalid(value) => write!(f, "invalid token: {:?}", value),
-            Errors::InvalidOperator(value) => write!(f, "invalid operator: {}", value),
-            Errors::InvalidInteger(value) => write!(f, "invalid integer: {}", value),
-            Errors::InvalidIdentifier(value) => write!(f, "invalid identifier: {}", value),
-            Errors::InvalidInfix => write!(f, "invalid_infix"),
-            Errors::NodeError => write!(f, "node_error"),
-            Errors::InvalidNumberOfArguments{got, want} => write!(f, "wrong number of arguments. got={}, want={}",got, want),
-            Errors::LenInvalidTypeError(value) => write!(f, "argument to len not supported got {}", value),
-            Errors::FirstTypeError(value) => write!(f, "argument to 'first' must be array, got {}", value),
-            Errors::LastTypeError(value) => write!(f, "argument to 'last' must be array, got {}", value),
-            Errors::RestTypeError(value) => write!(f, "argument to 'rest' must be array, got {}", value),
-            Errors::PushTypeError(value) => write!(f, "argument to 'push' must be array, got {}", value)
+            ErrorKind::IllegalCharacter(value) => write!(f, "illegal character: {}", value),
+            ErrorKind::UnterminatedString => write!(f, "unterminated string"),
+            ErrorKind::UnterminatedComment => write!(f, "unterminated comment"),
+            ErrorKind::InvalidEscape(value) => write!(f, "invalid escape sequence: {}", value),
+            ErrorKind::IntegerOutOfRange(value) => write!(f, "integer literal is out of range: {}", value),
+            ErrorKind::InvalidNumberLiteral(value) => write!(f, "invalid number literal: {}", value),
+            ErrorKind::TokenInvalid(value) => match value.token_type {
+                TokenKind::EOF => write!(f, "unexpected end of input"),
+                _ => write!(f, "invalid token: {}", value.literal),
+            },
+            ErrorKind::UnexpectedToken{want, got} => match got.token_type {
+                TokenKind::EOF => write!(f, "expected {:?} but got end of input", want),
+                _ => write!(f, "expected {:?} but got {}", want, got.literal),
+            },
+            ErrorKind::InvalidOperator(value) => write!(f, "invalid operator: {}", value),
+            ErrorKind::InvalidInteger(value) => write!(f, "invalid integer: {}", value),
+            ErrorKind::InvalidIdentifier(value) => write!(f, "invalid identifier: {}", value),
+            ErrorKind::InvalidInfix => write!(f, "invalid_infix"),
+            ErrorKind::NodeError => write!(f, "node_error"),
+            ErrorKind::InvalidNumberOfArguments{got, want} => write!(f, "wrong number of arguments. got={}, want={}",got, want),
+            ErrorKind::LenInvalidTypeError(value) => write!(f, "argument to len not supported got {}", value),
+            ErrorKind::FirstTypeError(value) => write!(f, "argument to 'first' must be array, got {}", value),
+            ErrorKind::LastTypeError(value) => write!(f, "argument to 'last' must be array, got {}", value),
+            ErrorKind::RestTypeError(value) => write!(f, "argument to 'rest' must be array, got {}", value),
+            ErrorKind::PushTypeError(value) => write!(f, "argument to 'push' must be array, got {}", value),
+            ErrorKind::GraphemesTypeError(value) => write!(f, "argument to 'graphemes' must be string, got {}", value),
+            ErrorKind::NumberTypeError{function, got} => write!(f, "argument to '{}' must be number, got {}", function, got),
+            ErrorKind::IntegerOverflow => write!(f, "integer overflow"),
+            ErrorKind::NotFinite(value) => write!(f, "{} can't be converted into an integer", value),
+            ErrorKind::DivisionByZero => write!(f, "division by zero"),
+            ErrorKind::ShiftOutOfRange(value) => write!(f, "shift amount is out of range: {}", value),
+            ErrorKind::OutsideLoop(value) => write!(f, "'{}' outside of a loop", value),
+            ErrorKind::NotIterable(value) => write!(f, "cannot iterate over {}", value),
+            ErrorKind::InvalidAssignTarget(value) => write!(f, "cannot assign to {}", value),
+            ErrorKind::UndeclaredVariable(value) => write!(f, "assignment to undeclared variable: {}", value),
+            ErrorKind::IndexOutOfRange(value) => write!(f, "index out of range: {}", value),
+            ErrorKind::UnusableHashKey(value) => write!(f, "unusable as hash key: {}", value),
+            ErrorKind::IndexAssignTypeError(value) => write!(f, "index assignment is not supported for {}", value),
+            ErrorKind::IndexTypeError{container, index} => write!(f, "index operator not supported: {}[{}]", container, index),
+            ErrorKind::RestParameterNotLast(value) => write!(f, "rest parameter must be the last one: {}", value),
+            ErrorKind::SpreadTypeError(value) => write!(f, "argument to '...' must be array, got {}", value),
+            ErrorKind::PositionalAfterKeyword => write!(f, "positional argument follows keyword argument"),
+            ErrorKind::UnknownKeywordArgument(value) => write!(f, "unexpected keyword argument: {}", value),
+            ErrorKind::DuplicateArgument(value) => write!(f, "multiple values for argument: {}", value),
+            ErrorKind::DestructureTypeError{pattern, got} => write!(f, "cannot destructure {} with {}", got, pattern),
+            ErrorKind::DestructureLengthMismatch{want, got, rest} => match rest {
+                true => write!(f, "expected at least {} elements but got {}", want, got),
+                false => write!(f, "expected {} elements but got {}", want, got),
+            },
+            ErrorKind::MissingHashKey(value) => write!(f, "key not found: {}", value),
+            ErrorKind::PatternMismatch{pattern, got} => write!(f, "{} does not match {}", got, pattern),
+            ErrorKind::NoMatchingArm(value) => write!(f, "no match arm for {}", value),
+            ErrorKind::UndefinedIdentifier{name, suggestions} => match suggestions.as_slice() {
+                [] => write!(f, "undefined identifier: {}", name),
+                _ => write!(f, "undefined identifier: {}. did you mean {}?", name, suggestions.join(" or ")),
+            },
+            ErrorKind::NotCallable(value) => write!(f, "not a function: {}", value),
+            ErrorKind::TryWithoutHandler => write!(f, "try needs catch or finally"),
+            ErrorKind::CallDepthExceeded(limit) => write!(f, "function calls are nested deeper than {}", limit),
+            ErrorKind::NestingTooDeep(limit) => write!(f, "code is nested deeper than {}", limit),
+            ErrorKind::Thrown(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+// every error knows where it happened in the source code.
+// errors which are raised without a position, such as the ones in builtins,
+// get the position of the expression which caused them by at().
+// the fields are boxed so that Result<_, Errors> stays as small as a pointer,
+// since every ? in the evaluator moves the error through each call.
+#[derive(Debug, PartialEq, Clone, Eq, Ord, PartialOrd)]
+pub struct Errors(Box<ErrorInner>);
+
+#[derive(Debug, PartialEq, Clone, Eq, Ord, PartialOrd)]
+pub struct ErrorInner {
+    pub kind: ErrorKind,
+    pub span: Span,
+    pub trace: Vec<Frame>, // the function calls which the error has left, from the innermost one
+}
+
+// a call of a function in a stack trace. span is the position of the call.
+#[derive(Debug, PartialEq, Clone, Eq, Ord, PartialOrd)]
+pub struct Frame {
+    pub function: String,
+    pub span: Span,
+}
+
+impl Errors {
+    pub fn new(kind: ErrorKind, span: Span) -> Self {
+        Errors(Box::new(ErrorInner{kind, span, trace: vec![]}))
+    }
+
+    // the error which is caused by an unexpected token.
+    pub fn token_invalid(token: &Token) -> Self {
+        Errors::new(ErrorKind::TokenInvalid(token.clone()), token.span)
+    }
+
+    // set the position unless the error already has one.
+    pub fn at(mut self, span: Span) -> Self {
+        if self.span.is_unknown() {
+            self.span = span;
         }
+        self
     }
-}
\ No newline at end of file
+
+    // add the call of function to the stack trace, when the error leaves the function.
+    pub fn traced(mut self, function: &str, span: Span) -> Self {
+        self.trace.push(Frame{function: function.to_string(), span});
+        self
+    }
+
+    // format the error as "file:line:col: message", followed by a line for each function call.
+    // the same call repeated by recursion is written once with the number of repeats.
+    pub fn report(&self, file: &str) -> String {
+        let mut report = if self.span.is_unknown() {
+            format!("{}: {}", file, self)
+        } else {
+            format!("{}:{}: {}", file, self.span, self)
+        };
+        let mut frames = self.trace.iter().peekable();
+        while let Some(frame) = frames.next() {
+            report.push_str(&format!("\n    in {}, called at {}:{}", frame.function, file, frame.span));
+            let mut repeats = 0;
+            // spans are always equal by ==, so the positions are compared.
+            while matches!(frames.peek(), Some(next) if next.function == frame.function && next.span.start == frame.span.start) {
+                frames.next();
+                repeats += 1;
+            }
+            if repeats > 0 {
+                report.push_str(&format!("\n    ... repeated {} more times", repeats));
+            }
+        }
+        report
+    }
+}
+
+impl Deref for Errors {
+    type Target = ErrorInner;
+
+    fn deref(&self) -> &ErrorInner {
+        &self.0
+    }
+}
+
+impl DerefMut for Errors {
+    fn deref_mut(&mut self) -> &mut ErrorInner {
+        &mut self.0
+    }
+}
+
+// the error whose position is not known yet.
+impl From<ErrorKind> for Errors {
+    fn from(kind: ErrorKind) -> Self {
+        Errors::new(kind, Span::default())
+    }
+}
+
+impl fmt::Display for Errors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
@@ -0,0 +1,156 @@
+// property tests which feed random inputs to the lexer and the parser.
+// whatever the input is, they must finish with tokens or errors, never with a panic.
+use crate::lexer::Lexer;
+use crate::parser::{Parser, MAX_NESTING};
+use crate::token::TokenKind;
+use crate::evaluator;
+
+// the number of random inputs which are tried in each test.
+const CASES: u64 = 2000;
+
+// words which are joined to make token streams.
+// they contain broken pieces too, such as a huge integer and an unclosed string.
+const WORDS: &[&str] = &[
+    "let", "fn", "true", "false", "if", "else", "return",
+    "while", "for", "in", "break", "continue", "match", "_",
+    "throw", "try", "catch", "finally",
+    "x", "y", "add", "len", "print",
+    "0", "5", "10", "2147483647", "2147483648", "99999999999999999999",
+    "3.14", "1e9", "2.5E-3", "1e", "1e400", "0xff", "0b102", "0o", "1_000", "0x1_0000_0000",
+    "\"foo\"", "\"\"", "\"unclosed", "\"\\q\"", "\"\\u{3042\"", "r\"\\\"", "\"\"\"", "\"${", "}\"", "\"a${x}b\"",
+    "=", "+", "-", "!", "*", "/", "<", ">", "==", "!=", "<=", ">=", "&&", "||", "&", "|",
+    "%", "**", "^", "~", "<<", ">>", "+=", "-=", "*=", "/=", "%=", "=>",
+    ",", ";", ":", "...", "(", ")", "{", "}", "[", "]",
+    "#", "//", "/*", "*/",
+    "@", "$", "あ", "\u{0}",
+];
+
+// xorshift, which makes reproducible random inputs without any crates.
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        // the state of xorshift must not be 0.
+        Random(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn bytes(&mut self) -> Vec<u8> {
+        let length = self.below(64);
+        (0..length).map(|_| self.next() as u8).collect()
+    }
+
+    fn token_stream(&mut self) -> String {
+        let length = self.below(48);
+        let separators = [" ", "", "\n"];
+        let mut stream = String::new();
+        for _ in 0..length {
+            stream.push_str(WORDS[self.below(WORDS.len())]);
+            stream.push_str(separators[self.below(separators.len())]);
+        }
+        stream
+    }
+}
+
+// read all tokens, and check that every call consumes at least one letter.
+fn lex_all(input: &str) {
+    let mut lexer = Lexer::new(input);
+    for _ in 0..=input.len() {
+        if let Ok(token) = lexer.next_token() {
+            if token.token_type == TokenKind::EOF {
+                return
+            }
+        }
+    }
+    panic!("lexer doesn't reach the end of {:?}", input);
+}
+
+fn parse_all(input: &str) {
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+    let (program, _) = parser.parse_program();
+    // a statement which is parsed must be able to be shown.
+    for statement in program.statements.iter() {
+        let _ = statement.to_string();
+    }
+}
+
+#[test]
+fn test_lexer_never_panics_on_random_bytes() {
+    for seed in 0..CASES {
+        let bytes = Random::new(seed).bytes();
+        lex_all(&String::from_utf8_lossy(&bytes));
+    }
+}
+
+#[test]
+fn test_lexer_never_panics_on_random_token_streams() {
+    for seed in 0..CASES {
+        lex_all(&Random::new(seed).token_stream());
+    }
+}
+
+#[test]
+fn test_parser_never_panics_on_random_bytes() {
+    for seed in 0..CASES {
+        let bytes = Random::new(seed).bytes();
+        parse_all(&String::from_utf8_lossy(&bytes));
+    }
+}
+
+#[test]
+fn test_parser_never_panics_on_random_token_streams() {
+    for seed in 0..CASES {
+        parse_all(&Random::new(seed).token_stream());
+    }
+}
+
+#[test]
+fn test_parser_never_panics_on_deep_nesting() {
+    // the parser needs the stack of the interpreter to reach the limit, which is larger than the one of a test.
+    let reports = std::thread::Builder::new().stack_size(evaluator::STACK_SIZE).spawn(|| {
+        // the first part is written once, and the second one is repeated.
+        let inputs = [("", "("), ("", "["), ("", "{"), ("", "-"), ("", "!"), ("", "2 ** "), ("", "fn() {"), ("", "while (x) {"),
+                      ("", "if (x) {"), ("", "f("), ("x", "["), ("let ", "["), ("match (x) { ", "["), ("", "\"${")];
+        inputs.iter().map(|(head, opening)| {
+            let input = format!("{}{}1", head, opening.repeat(50_000));
+            lex_all(&input);
+            parse_all(&input);
+            let lexer = Lexer::new(&input);
+            let mut parser = Parser::new(lexer);
+            let errors = parser.parse_program().1;
+            format!("{}", errors[0])
+        }).collect::<Vec<_>>()
+    }).unwrap().join().unwrap();
+    for report in reports.iter() {
+        assert_eq!(*report, format!("code is nested deeper than {}", MAX_NESTING));
+    }
+}
+
+#[test]
+fn test_parser_reports_broken_inputs() {
+    let tests = vec![
+        ("fn(1) {}", "invalid token: 1"),
+        ("fn(x {}", "expected RPAREN but got {"),
+        ("[1, 2", "expected RBRACKET but got end of input"),
+        ("2147483648", "integer literal is out of range: 2147483648"),
+        ("let x = 1 @ 2;", "illegal character: @"),
+        ("let x = \"★\" + ★;", "illegal character: ★"),
+        ];
+    for (input, expected) in tests.iter() {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let errors = parser.parse_program().1;
+        assert_eq!(format!("{}", errors[0]), *expected);
+    }
+}
@@ -1,11 +1,21 @@
-use super::token::{Token, TokenKind, get_keyword};
+extern crate unicode_xid;
+
+use unicode_xid::UnicodeXID;
+
+use super::token::{Token, TokenKind, Span, get_keyword};
+use super::errors::{Errors, ErrorKind};
 
 #[derive(Debug, Clone)]
 pub struct Lexer<'a>  {
     input:        &'a str,
     position:     usize, // current input position
     read_position: usize, // next input position
-    ch:           u8, // a letter which is currently read
+    ch:           char, // a letter which is currently read
+    line:         usize, // the line of ch
+    column:       usize, // the column of ch
+    keep_comments: bool, // if true, comments are returned as tokens
+    templates:    Vec<(usize, bool)>, // the depth of braces and whether the string is in triple quotes,
+                                      // for each interpolation which is open
 }
 
 impl<'a>  Lexer<'a>  {
@@ -14,165 +24,498 @@ impl<'a>  Lexer<'a>  {
                       input,
                       position: 0,
                       read_position: 0,
-                      ch: 0
+                      ch: '\0',
+                      line: 1,
+                      column: 0,
+                      keep_comments: false,
+                      templates: Vec::new(),
                     };
         l.read_char();
         return l;
     }
 
+    // the lexer which returns comments as COMMENT tokens instead of skipping them,
+    // for tools which need to know where comments are, such as formatters.
+    pub fn with_comments(input: &'a str) -> Self {
+        let mut l = Lexer::new(input);
+        l.keep_comments = true;
+        l
+    }
+
     fn read_char(&mut self) {
-    if self.read_position >= self.input.len() {
-        self.ch = 0;
+    // the letter which is left behind decides where the next one is.
+    if self.ch == '\n' {
+        self.line += 1;
+        self.column = 1;
     } else {
-        self.ch = self.input.as_bytes()[self.read_position];
-        }
+        self.column += 1;
+    }
     self.position = self.read_position;
-    self.read_position += 1;
+    match self.input.get(self.read_position..).and_then(|rest| rest.chars().next()) {
+        Some(ch) => {
+            self.ch = ch;
+            self.read_position += ch.len_utf8();
+        },
+        None => {
+            self.ch = '\0';
+            self.read_position += 1;
+        }
+    }
     }
 
-    fn read_string(&mut self) -> String {
-        let position = self.position + 1;
+    // read a string literal from the opening quote to the closing quote.
+    // a raw string (r"...") keeps backslashes as they are,
+    // and a string in triple quotes ("""...""") can contain quotes and new lines as they are.
+    // a string which contains ${...} is split into a TemplateHead, expressions, TemplateMiddles and a TemplateTail.
+    fn read_string(&mut self, raw: bool) -> Result<Token, Errors> {
+        let triple = self.peek_char() == '"' && self.peek_second_char() == '"';
+        if triple {
+            self.read_char();
+            self.read_char();
+        }
+        self.read_string_part(raw, triple, TokenKind::STRING, TokenKind::TemplateHead)
+    }
+
+    // read the rest of a string from '}' which closes an interpolation.
+    fn read_template_rest(&mut self) -> Result<Token, Errors> {
+        let (_, triple) = self.templates.pop().unwrap_or_default();
+        self.read_string_part(false, triple, TokenKind::TemplateTail, TokenKind::TemplateMiddle)
+    }
+
+    // read letters until the closing quote, whose token is `closed`,
+    // or until '${' which starts an interpolation, whose token is `open`.
+    fn read_string_part(&mut self, raw: bool, triple: bool, closed: TokenKind, open: TokenKind) -> Result<Token, Errors> {
+        let mut string = String::new();
+        // a broken escape is reported after the whole string is read,
+        // so that the next token starts after the closing quote.
+        let mut error = None;
+        let token_type;
         loop {
             self.read_char();
-            if self.ch == b'"' || self.ch == 0{
-            break;
+            match self.ch {
+                '\0' => return Err(ErrorKind::UnterminatedString.into()),
+                '"' if !triple => {
+                    token_type = closed;
+                    break;
+                },
+                '"' if self.peek_char() == '"' && self.peek_second_char() == '"' => {
+                    self.read_char();
+                    self.read_char();
+                    token_type = closed;
+                    break;
+                },
+                '$' if !raw && self.peek_char() == '{' => {
+                    self.read_char();
+                    self.templates.push((0, triple));
+                    token_type = open;
+                    break;
+                },
+                '\\' if !raw => {
+                    match self.read_escape() {
+                        Ok(ch) => string.push(ch),
+                        Err(err) => {
+                            error = error.or(Some(err));
+                        }
+                    }
+                },
+                ch => string.push(ch),
             }
         }
-        self.input[position..self.position].to_string()
+        // skip the closing quote or '{'.
+        self.read_char();
+        match error {
+            Some(error) => Err(error),
+            None => Ok(Token {
+                token_type,
+                literal: string,
+                span: Span::default(),
+            })
+        }
     }
 
-    fn peek_char(&mut self) -> u8 {
-        if self.read_position >= self.input.len(){
-            return 0
-        } else{
-            return self.input.as_bytes()[self.read_position]
+    // read an escape sequence which starts with the current backslash,
+    // and return the letter which it stands for.
+    fn read_escape(&mut self) -> Result<char, Errors> {
+        let line = self.line;
+        let column = self.column;
+        let start = self.position;
+        self.read_char();
+        let escaped = match self.ch {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '$' => Some('$'),
+            // \u{...} is a unicode code point written in hex.
+            'u' if self.peek_char() == '{' => {
+                self.read_char();
+                let mut hex = String::new();
+                while self.peek_char().is_ascii_hexdigit() {
+                    self.read_char();
+                    hex.push(self.ch);
+                }
+                if self.peek_char() == '}' && !hex.is_empty() && hex.len() <= 6 {
+                    self.read_char();
+                    u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32)
+                } else {
+                    None
+                }
+            },
+            _ => None
+        };
+        match escaped {
+            Some(ch) => Ok(ch),
+            None => {
+                // the end of input is reported as an unterminated string instead.
+                let end = self.read_position.min(self.input.len());
+                Err(Errors::new(ErrorKind::InvalidEscape(self.input[start..end].to_string()),
+                                Span::new(line, column, start, end)))
+            }
         }
     }
 
-    pub fn new_token(token_type: TokenKind, ch: u8)-> Token {
+    fn peek_char(&self) -> char {
+        self.input.get(self.read_position..)
+                  .and_then(|rest| rest.chars().next())
+                  .unwrap_or('\0')
+    }
+
+    fn peek_second_char(&self) -> char {
+        self.input.get(self.read_position..)
+                  .and_then(|rest| rest.chars().nth(1))
+                  .unwrap_or('\0')
+    }
+
+    pub fn new_token(token_type: TokenKind, ch: char)-> Token {
         Token {
               token_type,
-              literal: String::from_utf8(vec![ch]).unwrap(),
+              literal: ch.to_string(),
+              span: Span::default(),
+        }
+    }
+
+    // the token of the current letter and the next one, such as '<='.
+    fn read_two_char_token(&mut self, token_type: TokenKind) -> Token {
+        let position = self.position;
+        self.read_char();
+        Token {
+            token_type,
+            literal: String::from(&self.input[position..self.read_position]),
+            span: Span::default(),
         }
     }
 
     fn read_identifier(&mut self) -> String {
         let position = self.position;
-        while Self::is_letter(&self.ch) {
+        while Self::is_letter(&self.ch) || UnicodeXID::is_xid_continue(self.ch) {
             self.read_char();
         }
-        self.input.get(position..self.position).unwrap().to_string()
+        self.input[position..self.position].to_string()
     }
 
-    fn read_number(&mut self) -> String {
+    // read an integer, or a float such as 3.14, 1e9 or 2.5E-3.
+    // integers can have a prefix of 0x, 0o or 0b, and digits can be separated by '_'.
+    fn read_number(&mut self) -> Token {
         let position = self.position;
-        while Self::is_digit(&self.ch) {
+        let mut token_type = TokenKind::INT;
+        if self.ch == '0' && matches!(self.peek_char(), 'x' | 'o' | 'b') {
+            self.read_char();
+            self.read_char();
+            // the digits are checked by the parser, so that 0xg or 0b2 is reported as one literal.
+            while UnicodeXID::is_xid_continue(self.ch) {
+                self.read_char();
+            }
+            return Token {
+                token_type,
+                literal: self.input[position..self.position].to_string(),
+                span: Span::default(),
+            }
+        }
+        self.read_digits();
+        if self.ch == '.' && Self::is_digit(&self.peek_char()) {
+            token_type = TokenKind::FLOAT;
+            self.read_char();
+            self.read_digits();
+        }
+        // 'e' is a part of the number only if the exponent follows it.
+        let signed = self.peek_char() == '+' || self.peek_char() == '-';
+        if (self.ch == 'e' || self.ch == 'E')
+            && (Self::is_digit(&self.peek_char()) || signed && Self::is_digit(&self.peek_second_char())) {
+            token_type = TokenKind::FLOAT;
+            self.read_char();
+            if signed {
+                self.read_char();
+            }
+            self.read_digits();
+        }
+        Token {
+            token_type,
+            literal: self.input[position..self.position].to_string(),
+            span: Span::default(),
+        }
+    }
+
+    // read decimal digits, which can be separated by '_' like 1_000_000.
+    fn read_digits(&mut self) {
+        while Self::is_digit(&self.ch) || self.ch == '_' {
             self.read_char();
         }
-        self.input.get(position..self.position).unwrap().to_string()
     }
 
-    fn is_letter(ch: &u8) -> bool {
-        let ch = char::from(*ch);
-        'a' <= ch && ch <= 'z' || 'A' <= ch && ch <= 'Z' || ch == '_'
+    // identifiers follow the unicode rule (XID_Start and XID_Continue),
+    // so that they can be written in any language, like 名前 or 合計2.
+    fn is_letter(ch: &char) -> bool {
+        UnicodeXID::is_xid_start(*ch) || *ch == '_'
     }
 
-    fn is_digit(ch: &u8) -> bool {
-        let ch = char::from(*ch);
-        '0' <= ch && ch <= '9'
+    fn is_digit(ch: &char) -> bool {
+        ch.is_ascii_digit()
     }
 
     fn skip_whitespace(&mut self) {
-    while self.ch == b' ' || self.ch == b'\t' || self.ch == b'\n' || self.ch == b'\r' {
+    while self.ch == ' ' || self.ch == '\t' || self.ch == '\n' || self.ch == '\r' {
         self.read_char();
         }
     }
 
-    pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+    // return the next token, or the error about the letters which can't be a token.
+    // the broken letters are consumed, so the next call goes on from the letter after them.
+    pub fn next_token(&mut self) -> Result<Token, Errors> {
+        loop {
+            self.skip_whitespace();
+            let line = self.line;
+            let column = self.column;
+            let start = self.position.min(self.input.len());
+            let token = self.read_token();
+            let span = Span::new(line, column, start, self.position.min(self.input.len()));
+            match token {
+                Ok(ref token) if token.token_type == TokenKind::COMMENT && !self.keep_comments => continue,
+                Ok(mut token) => {
+                    token.span = span;
+                    return Ok(token)
+                },
+                Err(error) => return Err(error.at(span))
+            }
+        }
+    }
+
+    // read a comment from '#' or '//' to the end of the line.
+    fn read_line_comment(&mut self) -> Token {
+        let position = self.position;
+        while self.ch != '\n' && self.ch != '\0' {
+            self.read_char();
+        }
+        Token {
+            token_type: TokenKind::COMMENT,
+            literal: self.input[position..self.position].to_string(),
+            span: Span::default(),
+        }
+    }
+
+    // read a comment from '/*' to '*/'. comments can be nested like /* a /* b */ c */.
+    fn read_block_comment(&mut self) -> Result<Token, Errors> {
+        let position = self.position;
+        let mut depth = 0;
+        loop {
+            if self.ch == '\0' {
+                return Err(ErrorKind::UnterminatedComment.into())
+            } else if self.ch == '/' && self.peek_char() == '*' {
+                depth += 1;
+                self.read_char();
+            } else if self.ch == '*' && self.peek_char() == '/' {
+                depth -= 1;
+                self.read_char();
+                if depth == 0 {
+                    self.read_char();
+                    break;
+                }
+            }
+            self.read_char();
+        }
+        Ok(Token {
+            token_type: TokenKind::COMMENT,
+            literal: self.input[position..self.position].to_string(),
+            span: Span::default(),
+        })
+    }
+
+    fn read_token(&mut self) -> Result<Token, Errors> {
         let token;
         match self.ch {
-            b'=' => {
+            '=' => {
                 // if peek_char is '=', the literal will be '==',
-                if self.peek_char() == b'='{
+                if self.peek_char() == '='{
                     let curent_position = self.position;
                     self.read_char();
                     token =  Token {//u8は一文字なので直接tokenに入れる。
                         token_type: TokenKind::EQ,
-                        literal: String::from(&self.input[curent_position..self.read_position])
+                        literal: String::from(&self.input[curent_position..self.read_position]),
+                        span: Span::default(),
                         }
+                } else if self.peek_char() == '>' {
+                    token = self.read_two_char_token(TokenKind::FatArrow);
                 } else{
                 token = Self::new_token(TokenKind::ASSIGN, self.ch);
                 }
             }
-            b'-' => {
+            '-' if self.peek_char() == '=' => {
+                token = self.read_two_char_token(TokenKind::MinusAssign);
+            }
+            '-' => {
                 token = Self::new_token(TokenKind::MINUS, self.ch);
             }
-            b'!' => {
-                if self.peek_char() == b'='{
+            '!' => {
+                if self.peek_char() == '='{
                     // if peek_char is '=', the literal will be '!=',
                     let curent_position = self.position;
                     self.read_char();
                     token =  Token {
                         token_type: TokenKind::NotEq,
-                        literal: String::from(&self.input[curent_position..self.read_position])
+                        literal: String::from(&self.input[curent_position..self.read_position]),
+                        span: Span::default(),
                         }
                 } else {
                 token = Self::new_token(TokenKind::BANG, self.ch);
                 }
             }
-            b'*' => {
+            '*' if self.peek_char() == '*' => {
+                token = self.read_two_char_token(TokenKind::POWER);
+            }
+            '*' if self.peek_char() == '=' => {
+                token = self.read_two_char_token(TokenKind::AsteriskAssign);
+            }
+            '*' => {
                 token = Self::new_token(TokenKind::ASTERISK, self.ch);
             }
-            b'/' => {
+            '%' if self.peek_char() == '=' => {
+                token = self.read_two_char_token(TokenKind::PercentAssign);
+            }
+            '%' => {
+                token = Self::new_token(TokenKind::PERCENT, self.ch);
+            }
+            '^' => {
+                token = Self::new_token(TokenKind::CARET, self.ch);
+            }
+            '~' => {
+                token = Self::new_token(TokenKind::TILDE, self.ch);
+            }
+            // read_char() is not implemented for comments,
+            // because they are read until the letter after them.
+            '#' => {
+                return Ok(self.read_line_comment())
+            }
+            '/' if self.peek_char() == '/' => {
+                return Ok(self.read_line_comment())
+            }
+            '/' if self.peek_char() == '*' => {
+                return self.read_block_comment()
+            }
+            '/' if self.peek_char() == '=' => {
+                token = self.read_two_char_token(TokenKind::SlashAssign);
+            }
+            '/' => {
                 token = Self::new_token(TokenKind::SLASH, self.ch);
             }
-            b'<' => {
+            '<' if self.peek_char() == '<' => {
+                token = self.read_two_char_token(TokenKind::ShiftLeft);
+            }
+            '<' if self.peek_char() == '=' => {
+                token = self.read_two_char_token(TokenKind::LtEq);
+            }
+            '<' => {
                 token = Self::new_token(TokenKind::LT, self.ch);
             }
-            b'>' => {
+            '>' if self.peek_char() == '>' => {
+                token = self.read_two_char_token(TokenKind::ShiftRight);
+            }
+            '>' if self.peek_char() == '=' => {
+                token = self.read_two_char_token(TokenKind::GtEq);
+            }
+            '>' => {
                 token = Self::new_token(TokenKind::GT, self.ch);
             }
-            b';' => {
+            '&' if self.peek_char() == '&' => {
+                token = self.read_two_char_token(TokenKind::AND);
+            }
+            '&' => {
+                token = Self::new_token(TokenKind::AMPERSAND, self.ch);
+            }
+            '|' if self.peek_char() == '|' => {
+                token = self.read_two_char_token(TokenKind::OR);
+            }
+            '|' => {
+                token = Self::new_token(TokenKind::PIPE, self.ch);
+            }
+            ';' => {
                 token = Self::new_token(TokenKind::SEMICOLON, self.ch);
             }
-            b'(' => {
+            '(' => {
                 token = Self::new_token(TokenKind::LPAREN, self.ch);
             }
-            b')' => {
+            ')' => {
                 token = Self::new_token(TokenKind::RPAREN, self.ch);
             }
-            b',' => {
+            ',' => {
                 token = Self::new_token(TokenKind::COMMA, self.ch);
             }
-            b'+' => {
+            '.' if self.peek_char() == '.' && self.peek_second_char() == '.' => {
+                let position = self.position;
+                self.read_char();
+                self.read_char();
+                token = Token {
+                    token_type: TokenKind::ELLIPSIS,
+                    literal: String::from(&self.input[position..self.read_position]),
+                    span: Span::default(),
+                };
+            }
+            '+' if self.peek_char() == '=' => {
+                token = self.read_two_char_token(TokenKind::PlusAssign);
+            }
+            '+' => {
                 token = Self::new_token(TokenKind::PLUS, self.ch);
             }
-            b'{' => {
+            '{' => {
+                if let Some((depth, _)) = self.templates.last_mut() {
+                    *depth += 1;
+                }
                 token = Self::new_token(TokenKind::LBRACE, self.ch);
             }
-            b'}' => {
+            // '}' which closes an interpolation goes back to the string.
+            '}' if matches!(self.templates.last(), Some((0, _))) => {
+                return self.read_template_rest()
+            }
+            '}' => {
+                if let Some((depth, _)) = self.templates.last_mut() {
+                    *depth -= 1;
+                }
                 token = Self::new_token(TokenKind::RBRACE, self.ch);
             }
-            b'[' => {
+            '[' => {
                 token = Self::new_token(TokenKind::LBRACKET, self.ch);
             }
-            b']' => {
+            ']' => {
                 token = Self::new_token(TokenKind::RBRACKET, self.ch);
             }
-            b':' => {
+            ':' => {
                 token = Self::new_token(TokenKind::COLON, self.ch);
             }
-            b'"' => {
-                token = Token {
-                token_type: TokenKind::STRING,
-                literal: self.read_string()
-                    }
+            // read_char() is not implemented for strings either,
+            // because the closing quote is skipped in read_string().
+            '"' => {
+                return self.read_string(false)
+            }
+            'r' if self.peek_char() == '"' => {
+                // skip 'r' which marks a raw string.
+                self.read_char();
+                return self.read_string(true)
             }
-            0 => {
+            '\0' => {
                 token = Token {
                        token_type:  TokenKind::EOF,
                        literal: String::from(""),
+                       span: Span::default(),
                 };
             }
             _   => {
@@ -183,22 +526,21 @@ impl<'a>  Lexer<'a>  {
                         let ident_token = get_keyword(&ident);
                             token =  Token {
                             token_type: ident_token,
-                            literal: ident
+                            literal: ident,
+                            span: Span::default(),
                             };
-                     return token
+                     return Ok(token)
                     } else if Self::is_digit(&self.ch) {
-                        token =  Token {
-                            token_type: TokenKind::INT,
-                            literal: self.read_number()
-                        };
-                        return token
+                        return Ok(self.read_number())
                     } else {
-                    token = Self::new_token(TokenKind::ILLEGAL, self.ch);
+                        let illegal = self.ch;
+                        self.read_char();
+                        return Err(ErrorKind::IllegalCharacter(illegal).into())
                            }
                     }
                 }
         self.read_char();
-        return token;
+        return Ok(token);
     }
 }
 
@@ -216,7 +558,7 @@ let add = fn(x, y){
    x + y;
 };
 let result = add (five, ten);
-!-/*5;
+!-/ *5;
 5 < 10 > 5;
 
 if (5 < 10) {
@@ -326,9 +668,227 @@ if (5 < 10) {
 
     let mut lexer = Lexer::new(input);
     for test in tests.iter() {
-        let _token = lexer.next_token();
+        let _token = lexer.next_token().unwrap();
         assert_eq!(_token.token_type,  test.0);
         assert_eq!(_token.literal, test.1);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_token_position() {
+        let input = "let x = 5;\n  x == \"ab\";";
+        let tests = vec![
+               (TokenKind::LET, 1, 1, 0, 3),
+               (TokenKind::IDENT, 1, 5, 4, 5),
+               (TokenKind::ASSIGN, 1, 7, 6, 7),
+               (TokenKind::INT, 1, 9, 8, 9),
+               (TokenKind::SEMICOLON, 1, 10, 9, 10),
+               (TokenKind::IDENT, 2, 3, 13, 14),
+               (TokenKind::EQ, 2, 5, 15, 17),
+               (TokenKind::STRING, 2, 8, 18, 22),
+               (TokenKind::SEMICOLON, 2, 12, 22, 23),
+               (TokenKind::EOF, 2, 13, 23, 23),
+               ];
+
+    let mut lexer = Lexer::new(input);
+    for test in tests.iter() {
+        let token = lexer.next_token().unwrap();
+        assert_eq!(token.token_type, test.0);
+        assert_eq!((token.span.line, token.span.column, token.span.start, token.span.end),
+                   (test.1, test.2, test.3, test.4));
+        }
+    }
+
+    #[test]
+    fn test_unicode_identifier() {
+        let input = "let 合計2 = \"値段\";\n_名前 + café";
+        let tests = vec![
+               (TokenKind::LET, "let", 1, 1),
+               (TokenKind::IDENT, "合計2", 1, 5),
+               (TokenKind::ASSIGN, "=", 1, 9),
+               (TokenKind::STRING, "値段", 1, 11),
+               (TokenKind::SEMICOLON, ";", 1, 15),
+               (TokenKind::IDENT, "_名前", 2, 1),
+               (TokenKind::PLUS, "+", 2, 5),
+               (TokenKind::IDENT, "café", 2, 7),
+               (TokenKind::EOF, "", 2, 11),
+               ];
+
+    let mut lexer = Lexer::new(input);
+    for test in tests.iter() {
+        let token = lexer.next_token().unwrap();
+        assert_eq!((token.token_type, token.literal.as_str(), token.span.line, token.span.column),
+                   (test.0, test.1, test.2, test.3));
+        }
+    }
+
+    #[test]
+    fn test_string_escape() {
+        let input = r#""a\"b\\c" "tab\there\nnew line" "\u{3042}\u{1F600}" r"C:\path\n" """{"key": "va\tlue"}""" r"""{"raw": "\n"}"""
+"""two
+lines""""#;
+        let tests = vec![
+               "a\"b\\c",
+               "tab\there\nnew line",
+               "あ😀",
+               "C:\\path\\n",
+               "{\"key\": \"va\tlue\"}",
+               "{\"raw\": \"\\n\"}",
+               "two\nlines",
+               ];
+
+    let mut lexer = Lexer::new(input);
+    for test in tests.iter() {
+        let token = lexer.next_token().unwrap();
+        assert_eq!(token.token_type, TokenKind::STRING);
+        assert_eq!(token.literal, *test);
+        }
+    assert_eq!(lexer.next_token().unwrap().token_type, TokenKind::EOF);
+    }
+
+    #[test]
+    fn test_template_string() {
+        let input = r#""hi ${name}!" "${ {"a": 1}["a"] }${"${x}"}" r"${x}" "\${x}""#;
+        let tests = vec![
+               (TokenKind::TemplateHead, "hi "),
+               (TokenKind::IDENT, "name"),
+               (TokenKind::TemplateTail, "!"),
+               (TokenKind::TemplateHead, ""),
+               // braces in an interpolation don't close it.
+               (TokenKind::LBRACE, "{"),
+               (TokenKind::STRING, "a"),
+               (TokenKind::COLON, ":"),
+               (TokenKind::INT, "1"),
+               (TokenKind::RBRACE, "}"),
+               (TokenKind::LBRACKET, "["),
+               (TokenKind::STRING, "a"),
+               (TokenKind::RBRACKET, "]"),
+               (TokenKind::TemplateMiddle, ""),
+               // a template string can be nested.
+               (TokenKind::TemplateHead, ""),
+               (TokenKind::IDENT, "x"),
+               (TokenKind::TemplateTail, ""),
+               (TokenKind::TemplateTail, ""),
+               (TokenKind::STRING, "${x}"),
+               (TokenKind::STRING, "${x}"),
+               (TokenKind::EOF, ""),
+               ];
+
+    let mut lexer = Lexer::new(input);
+    for test in tests.iter() {
+        let token = lexer.next_token().unwrap();
+        assert_eq!((token.token_type, token.literal.as_str()), *test);
+        }
+    }
+
+    #[test]
+    fn test_string_error() {
+        let tests = vec![
+               (r#""a\qb" 1"#, "1:3: invalid escape sequence: \\q"),
+               (r#""\u{110000}" 1"#, "1:2: invalid escape sequence: \\u{110000}"),
+               (r#""\u{}" 1"#, "1:2: invalid escape sequence: \\u{"),
+               (r#""\u00e9" 1"#, "1:2: invalid escape sequence: \\u"),
+               ];
+        for (input, expected) in tests.iter() {
+            let mut lexer = Lexer::new(input);
+            let error = lexer.next_token().unwrap_err();
+            assert_eq!(format!("{}: {}", error.span, error), *expected);
+            // lexing goes on from the end of the broken string.
+            assert_eq!(lexer.next_token().unwrap().literal, "1");
+        }
+
+        let tests = vec![r#""abc"#, r#""abc\"#, r#"r"abc\"#, r#""""abc""#];
+        for input in tests.iter() {
+            let mut lexer = Lexer::new(input);
+            let error = lexer.next_token().unwrap_err();
+            assert_eq!(format!("{}: {}", error.span, error), "1:1: unterminated string");
+            assert_eq!(lexer.next_token().unwrap().token_type, TokenKind::EOF);
+        }
+    }
+
+    #[test]
+    fn test_float() {
+        let input = "3.14 1e9 2.5E-3 6.02e+23 1e 2e-";
+        let tests = vec![
+               (TokenKind::FLOAT, "3.14"),
+               (TokenKind::FLOAT, "1e9"),
+               (TokenKind::FLOAT, "2.5E-3"),
+               (TokenKind::FLOAT, "6.02e+23"),
+               // an exponent without digits is not a part of the number.
+               (TokenKind::INT, "1"),
+               (TokenKind::IDENT, "e"),
+               (TokenKind::INT, "2"),
+               (TokenKind::IDENT, "e"),
+               (TokenKind::MINUS, "-"),
+               (TokenKind::EOF, ""),
+               ];
+
+    let mut lexer = Lexer::new(input);
+    for test in tests.iter() {
+        let token = lexer.next_token().unwrap();
+        assert_eq!((token.token_type, token.literal.as_str()), *test);
+        }
+    }
+
+    #[test]
+    fn test_number_prefix_and_separator() {
+        let input = "0xff_ff 0o17 0b1010 1_000_000 3_141.592_6 0xg 0 x";
+        let tests = vec![
+               (TokenKind::INT, "0xff_ff"),
+               (TokenKind::INT, "0o17"),
+               (TokenKind::INT, "0b1010"),
+               (TokenKind::INT, "1_000_000"),
+               (TokenKind::FLOAT, "3_141.592_6"),
+               (TokenKind::INT, "0xg"),
+               (TokenKind::INT, "0"),
+               (TokenKind::IDENT, "x"),
+               (TokenKind::EOF, ""),
+               ];
+
+    let mut lexer = Lexer::new(input);
+    for test in tests.iter() {
+        let token = lexer.next_token().unwrap();
+        assert_eq!((token.token_type, token.literal.as_str()), *test);
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "# the first value.
+let a = 1; // a is 1
+/* a /* nested */ comment */ a / 2 # end";
+        let tests = vec![
+               (TokenKind::COMMENT, "# the first value."),
+               (TokenKind::LET, "let"),
+               (TokenKind::IDENT, "a"),
+               (TokenKind::ASSIGN, "="),
+               (TokenKind::INT, "1"),
+               (TokenKind::SEMICOLON, ";"),
+               (TokenKind::COMMENT, "// a is 1"),
+               (TokenKind::COMMENT, "/* a /* nested */ comment */"),
+               (TokenKind::IDENT, "a"),
+               (TokenKind::SLASH, "/"),
+               (TokenKind::INT, "2"),
+               (TokenKind::COMMENT, "# end"),
+               (TokenKind::EOF, ""),
+               ];
+
+    // comments are returned only when they are required.
+    let mut lexer = Lexer::with_comments(input);
+    for test in tests.iter() {
+        let token = lexer.next_token().unwrap();
+        assert_eq!((token.token_type, token.literal.as_str()), *test);
+        }
+    let mut lexer = Lexer::new(input);
+    for test in tests.iter().filter(|test| test.0 != TokenKind::COMMENT) {
+        let token = lexer.next_token().unwrap();
+        assert_eq!((token.token_type, token.literal.as_str()), *test);
+        }
+
+    let mut lexer = Lexer::new("1 /* a /* b */ c");
+    assert_eq!(lexer.next_token().unwrap().literal, "1");
+    let error = lexer.next_token().unwrap_err();
+    assert_eq!(format!("{}: {}", error.span, error), "1:3: unterminated comment");
+    assert_eq!(lexer.next_token().unwrap().token_type, TokenKind::EOF);
+    }
+}
@@ -6,4 +6,6 @@ mod parser;
 mod errors;
 mod object;
 mod evaluator;
-mod builtins;
\ No newline at end of file
+mod builtins;
+#[cfg(test)]
+mod fuzz;
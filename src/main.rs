@@ -1,3 +1,6 @@
+use std::env;
+use std::thread;
+
 mod lexer;
 mod repl;
 mod token;
@@ -9,6 +12,22 @@ mod object;
 mod builtins;
 
 fn main() {
+    // the evaluator recurses as deep as scripts call functions, so it runs on a thread with a large stack.
+    let interpreter = thread::Builder::new()
+                                      .stack_size(evaluator::STACK_SIZE)
+                                      .spawn(run)
+                                      .expect("failed to start the interpreter");
+    if interpreter.join().is_err() {
+        std::process::exit(101);
+    }
+}
+
+fn run() {
+    // if a file is given, evaluate it instead of starting the console.
+    if let Some(path) = env::args().nth(1) {
+        repl::execute(&path);
+        return;
+    }
     println!("Hello! This is the Monkey programming language!");
     println!("Feel free to type in commands");
     println!("");
@@ -1,27 +1,39 @@
 use std::fmt;
 use std::collections::BTreeMap;
+use std::rc::Rc;
 
 use super::evaluator::{Environment};
-use super::ast::{Expression, Statement};
+use super::ast::{Statement, Float, Parameter};
 use super::errors::{Errors};
+use super::builtins::{BuiltinFunction};
 
+// the derived equality compares the structure, where floats are compared by their bits.
+// the == of the language is the one of the evaluator.
 #[derive(Debug,PartialEq, Clone, Eq, Ord, PartialOrd)]
 pub enum Object {
     Identifier(String),
     String(String),
     Integer(i32),
+    Float(Float),
     Boolean(bool),
     Return(Box<Object>),
+    Break,
+    Continue,
     Let(Box<Object>),
     Array(Vec<Object>),
     Hash(BTreeMap<Box<HashKey>, Box<HashPair>>),
-    Function{params: Vec<Expression>,
-             body: Statement,
+    Function{name: Option<String>,
+             params: Rc<Vec<Parameter>>,
+             body: Rc<Statement>,
              env: Environment
             },
+    // params are the names which keyword arguments can use. they are empty if the builtin takes none.
     Builtin{
-        func: fn(Vec<Object>) -> Object
+        func: BuiltinFunction,
+        params: &'static [&'static str]
        },
+    // an error which is held as a value, such as the one caught by try.
+    // evaluation is stopped only by Err, never by this.
     Error(Errors),
     Null,
     Default
@@ -33,8 +45,11 @@ impl fmt::Display for Object {
            Object::Identifier(value) => write!(f, "{}", value),
            Object::String(value) => write!(f, "{}", value),
            Object::Integer(value) => write!(f, "{}", value),
+           Object::Float(value) => write!(f, "{}", value),
            Object::Boolean(value) => write!(f, "{}", value),
            Object::Return(value) => write!(f, "{}", value),
+           Object::Break => write!(f, "break"),
+           Object::Continue => write!(f, "continue"),
            Object::Let(value) => write!(f, "{}", value),
            Object::Hash(tree) => {
             match tree {
@@ -42,11 +57,10 @@ impl fmt::Display for Object {
                 _ =>  unreachable!()}
             },
            Object::Array(value) => write!(f, "[{}]", value.iter().map(|expression| format!("{}", &expression)).collect::<Vec<_>>().join(", ")),
-           Object::Function{params, body, env} => write!(f, "{} {} {:?}", params.iter().map(|expression| format!("{}", &expression)).collect::<Vec<_>>().join(", ")
+           Object::Function{name, params, body, ..} => write!(f, "fn {}({}) {{{}}}", name.as_deref().unwrap_or(""), params.iter().map(|expression| format!("{}", &expression)).collect::<Vec<_>>().join(", ")
                                                                         , body
-                                                                        , env
                                                         ),
-           Object::Builtin{func: _} => write!(f, "builtin functions"),
+           Object::Builtin{..} => write!(f, "builtin functions"),
            Object::Null => write!(f, ""),
            Object::Default => write!(f, "default"),
            Object::Error(value) => write!(f, "{}", value)
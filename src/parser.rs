@@ -1,23 +1,34 @@
 use std::collections::BTreeMap;
-use super::token::{Token, TokenKind};
+use std::rc::Rc;
+use super::token::{Token, TokenKind, Span};
 use super::lexer;
-use super::errors::{Errors};
+use super::errors::{Errors, ErrorKind};
 use super::ast::{Program, Statement, Statement::LetStatement,
-                 Expression, Precedence};
+                 Expression, Precedence, Float, Parameter, Pattern, MatchArm};
+
+// the number of expressions, blocks and patterns which can be nested in each other.
+// the parser is recursive, so deeper nesting is an error instead of overflowing the stack.
+pub const MAX_NESTING: usize = 256;
 
 #[derive(Debug, Clone)]
 pub struct Parser<'a>  {
     lexer: lexer::Lexer<'a>,
     current_token: Token,
     next_token: Token,
+    errors: Vec<Errors>, // syntax errors which have been found so far
+    loop_depth: usize, // the number of loops around the current token, for break and continue
+    nesting: usize, // the number of expressions, blocks and patterns around the current token
 }
 
 impl<'a>  Parser<'a>  {
     pub fn new(l: lexer::Lexer<'a>) -> Self {
         let mut p = Parser{
             lexer: l,
-            current_token: Token{token_type: TokenKind::DEFAULT, literal: "default".to_string() },
-            next_token: Token{token_type: TokenKind::DEFAULT, literal: "default".to_string() },
+            current_token: Token{token_type: TokenKind::DEFAULT, literal: "default".to_string(), span: Span::default() },
+            next_token: Token{token_type: TokenKind::DEFAULT, literal: "default".to_string(), span: Span::default() },
+            errors: vec![],
+            loop_depth: 0,
+            nesting: 0,
         };
         p.next_token();
         p.next_token();
@@ -26,19 +37,92 @@ impl<'a>  Parser<'a>  {
 
     pub fn next_token(&mut self) {
         self.current_token = self.next_token.clone();
-        self.next_token = self.lexer.next_token();
+        // letters which can't be tokens are reported, and parsing goes on without them.
+        loop {
+            match self.lexer.next_token() {
+                // comments don't have any meaning for programs.
+                Ok(ref token) if token.token_type == TokenKind::COMMENT => continue,
+                Ok(token) => {
+                    self.next_token = token;
+                    return
+                },
+                Err(error) => self.record_error(error),
+            }
+        }
     }
 
-    pub fn parse_program(&mut self) -> Result<Program, Errors> {
+    // parse the whole input without stopping at syntax errors, and return every syntax error with the program.
+    // a broken statement is skipped, so the program contains only the statements which could be parsed.
+    pub fn parse_program(&mut self) -> (Program, Vec<Errors>) {
         let mut statements: Vec<Statement> = vec![];
 
         // read token until it reaches at the end of sentence.
         while !self.is_current_token(TokenKind::EOF){
-            let statement = self.parse_statement()?;
-            statements.push(statement);
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    self.record_error(error);
+                    self.synchronize();
+                    // '}' which doesn't close any block can't be a part of statements.
+                    while self.is_current_token(TokenKind::RBRACE) {
+                        self.next_token();
+                        self.synchronize();
+                    }
+                    continue;
+                }
+            }
             self.next_token();
         };
-        Ok(Program {statements: statements})
+        // lexical errors are found a little earlier than syntax errors, because of the lookahead.
+        self.errors.sort_by_key(|error| error.span.start);
+        (Program {statements: statements}, self.errors.clone())
+    }
+
+    // only the first error is kept for one position,
+    // for example when the input ends inside of nested blocks.
+    fn record_error(&mut self, error: Errors) {
+        if let Some(last) = self.errors.last() {
+            if last.span.start == error.span.start {
+                return
+            }
+        }
+        self.errors.push(error);
+    }
+
+    // parse a part which is nested one level deeper, such as an expression in parentheses.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, Errors>) -> Result<T, Errors> {
+        if self.nesting >= MAX_NESTING {
+            return Err(Errors::new(ErrorKind::NestingTooDeep(MAX_NESTING), self.current_token.span))
+        }
+        self.nesting += 1;
+        let result = parse(self);
+        self.nesting -= 1;
+        result
+    }
+
+    // skip tokens until the beginning of the next statement.
+    // it stops at '}' which closes the current block, so that the block can be closed normally.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.current_token.token_type {
+                TokenKind::EOF => return,
+                TokenKind::RBRACE if depth == 0 => return,
+                TokenKind::LET | TokenKind::RETURN if depth == 0 => return,
+                TokenKind::SEMICOLON if depth == 0 => {
+                    self.next_token();
+                    return
+                },
+                TokenKind::LBRACE | TokenKind::LPAREN | TokenKind::LBRACKET => depth += 1,
+                TokenKind::RBRACE | TokenKind::RPAREN | TokenKind::RBRACKET => {
+                    if depth > 0 {
+                        depth -= 1;
+                    }
+                },
+                _ => {}
+            }
+            self.next_token();
+        }
     }
 
     fn parse_statement(&mut self) -> Result<Statement, Errors> {
@@ -49,6 +133,21 @@ impl<'a>  Parser<'a>  {
             TokenKind::RETURN => {
                 Ok(self.parse_return_statement()?)
             },
+            TokenKind::FUNCTION if self.is_next_token(TokenKind::IDENT) => {
+                Ok(self.parse_function_statement()?)
+            },
+            TokenKind::WHILE => {
+                Ok(self.parse_while_statement()?)
+            },
+            TokenKind::FOR => {
+                Ok(self.parse_for_statement()?)
+            },
+            TokenKind::BREAK | TokenKind::CONTINUE => {
+                Ok(self.parse_loop_control_statement()?)
+            },
+            TokenKind::THROW => {
+                Ok(self.parse_throw_statement()?)
+            },
             _ => {
                 Ok(self.parse_expression_statement()?)
             }
@@ -56,17 +155,14 @@ impl<'a>  Parser<'a>  {
     }
 
     fn parse_let_statement(&mut self) -> Result<Statement, Errors> {
+        let start = self.current_token.span;
         // Since current token is let, next_token() is implemented in order to read identifier next to 'let'.
         self.next_token();
 
-        // If current token is identifier, next token must be '==' or '!='
-        if !self.is_current_token(TokenKind::IDENT) || self.expect_next_token(TokenKind::IDENT){
-            return Err(Errors::TokenInvalid(self.next_token.clone()))
-        }
-        let identifier = Expression::Identifier(self.current_token.literal.clone());
-        // If there isn't assign next to identifier, return error.
+        let pattern = self.parse_pattern()?;
+        // If there isn't assign next to pattern, return error.
         if !self.expect_next_token(TokenKind::ASSIGN) {
-            return Err(Errors::TokenInvalid(self.next_token.clone()))
+            return Err(Errors::token_invalid(&self.next_token))
         }
         // skip a assign token
         self.next_token();
@@ -77,22 +173,193 @@ impl<'a>  Parser<'a>  {
             self.next_token();
         }
         let stmt = LetStatement {
-                identifier: identifier,
+                pattern: pattern,
+                span: start.to(self.current_token.span),
                 value: stmt_value
         };
         return Ok(stmt)
     }
 
+    // an identifier, _, a literal, [a, b, ...rest] or {"key": pattern}. patterns can be nested.
+    fn parse_pattern(&mut self) -> Result<Pattern, Errors> {
+        self.nested(|parser| parser.parse_pattern_inner())
+    }
+
+    fn parse_pattern_inner(&mut self) -> Result<Pattern, Errors> {
+        let start = self.current_token.span;
+        match self.current_token.token_type {
+            TokenKind::IDENT if self.current_token.literal == "_" => Ok(Pattern::Wildcard(start)),
+            TokenKind::IDENT => Ok(Pattern::Identifier(self.current_token.literal.clone(), start)),
+            // a literal is parsed as a prefix expression, so that -1 is allowed but 1 + 2 is not.
+            TokenKind::INT | TokenKind::FLOAT | TokenKind::STRING | TokenKind::TRUE | TokenKind::FALSE | TokenKind::MINUS => {
+                Ok(Pattern::Literal(self.parse_expression(Precedence::PREFIX)?))
+            },
+            TokenKind::LBRACKET => {
+                let mut elements = vec![];
+                let mut rest = None;
+                while !self.is_next_token(TokenKind::RBRACKET) {
+                    self.next_token();
+                    // ...rest must be the last element.
+                    if self.is_current_token(TokenKind::ELLIPSIS) {
+                        self.expect_next(TokenKind::IDENT)?;
+                        rest = Some(self.current_token.literal.clone());
+                        break;
+                    }
+                    elements.push(self.parse_pattern()?);
+                    if !self.is_next_token(TokenKind::RBRACKET) {
+                        self.expect_next(TokenKind::COMMA)?;
+                    }
+                }
+                self.expect_next(TokenKind::RBRACKET)?;
+                Ok(Pattern::Array{elements, rest, span: start.to(self.current_token.span)})
+            },
+            TokenKind::LBRACE => {
+                let mut pairs = vec![];
+                while !self.is_next_token(TokenKind::RBRACE) {
+                    self.next_token();
+                    let key = self.parse_expression(Precedence::LOWEST)?;
+                    self.expect_next(TokenKind::COLON)?;
+                    self.next_token();
+                    pairs.push((key, self.parse_pattern()?));
+                    if !self.is_next_token(TokenKind::RBRACE) {
+                        self.expect_next(TokenKind::COMMA)?;
+                    }
+                }
+                self.expect_next(TokenKind::RBRACE)?;
+                Ok(Pattern::Hash{pairs, span: start.to(self.current_token.span)})
+            },
+            _ => Err(Errors::token_invalid(&self.current_token))
+        }
+    }
+
     fn parse_return_statement(&mut self) -> Result<Statement, Errors> {
+        let start = self.current_token.span;
         // skip return value and then read value next to return.
         self.next_token();
         let return_value = self.parse_expression(Precedence::LOWEST)?;
+        let span = start.to(return_value.span());
 
-        // read token until it reaches at the end of sentence.
-        while !self.is_current_token(TokenKind::SEMICOLON) {
-            self.next_token()
+        if self.is_next_token(TokenKind::SEMICOLON) {
+            self.next_token();
+        }
+        return Ok(Statement::Return(return_value, span))
+    }
+
+    // fn name(params) { body } is the function literal with its name.
+    fn parse_function_statement(&mut self) -> Result<Statement, Errors> {
+        let start = self.current_token.span;
+        self.next_token();
+        let function_name = self.current_token.literal.clone();
+        let mut function = self.parse_function_expression()?;
+        if let Expression::FunctionLiteral{name, span, ..} = &mut function {
+            *name = Some(function_name);
+            *span = start.to(*span);
+        }
+        if self.is_next_token(TokenKind::SEMICOLON) {
+            self.next_token();
+        }
+        Ok(Statement::ExpressionStatement(function))
+    }
+
+    fn parse_while_statement(&mut self) -> Result<Statement, Errors> {
+        let start = self.current_token.span;
+        self.expect_next(TokenKind::LPAREN)?;
+        let condition = self.parse_expression(Precedence::LOWEST)?;
+        self.expect_next(TokenKind::LBRACE)?;
+        let body = self.parse_loop_body()?;
+        let span = start.to(body.span());
+        if self.is_next_token(TokenKind::SEMICOLON) {
+            self.next_token();
+        }
+        Ok(Statement::While{condition, body: Box::new(body), span})
+    }
+
+    // for (x in xs) {...} or for (i, x in xs) {...}
+    fn parse_for_statement(&mut self) -> Result<Statement, Errors> {
+        let start = self.current_token.span;
+        self.expect_next(TokenKind::LPAREN)?;
+        let mut variables = vec![];
+        self.expect_next(TokenKind::IDENT)?;
+        variables.push(Expression::Identifier(self.current_token.literal.clone(), self.current_token.span));
+        if self.expect_next_token(TokenKind::COMMA) {
+            self.expect_next(TokenKind::IDENT)?;
+            variables.push(Expression::Identifier(self.current_token.literal.clone(), self.current_token.span));
+        }
+        self.expect_next(TokenKind::IN)?;
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::LOWEST)?;
+        self.expect_next(TokenKind::RPAREN)?;
+        self.expect_next(TokenKind::LBRACE)?;
+        let body = self.parse_loop_body()?;
+        let span = start.to(body.span());
+        if self.is_next_token(TokenKind::SEMICOLON) {
+            self.next_token();
+        }
+        Ok(Statement::For{variables, iterable, body: Box::new(body), span})
+    }
+
+    fn parse_loop_body(&mut self) -> Result<Statement, Errors> {
+        self.loop_depth += 1;
+        let body = self.parse_block_statements(TokenKind::LBRACE);
+        self.loop_depth -= 1;
+        body
+    }
+
+    // break and continue can be written only in loops.
+    fn parse_loop_control_statement(&mut self) -> Result<Statement, Errors> {
+        let span = self.current_token.span;
+        if self.loop_depth == 0 {
+            return Err(Errors::new(ErrorKind::OutsideLoop(self.current_token.literal.clone()), span))
+        }
+        let statement = match self.current_token.token_type {
+            TokenKind::BREAK => Statement::Break(span),
+            _ => Statement::Continue(span)
+        };
+        if self.is_next_token(TokenKind::SEMICOLON) {
+            self.next_token();
         }
-        return Ok(Statement::Return(return_value))
+        Ok(statement)
+    }
+
+    fn parse_throw_statement(&mut self) -> Result<Statement, Errors> {
+        let start = self.current_token.span;
+        self.next_token();
+        let value = self.parse_expression(Precedence::LOWEST)?;
+        let span = start.to(value.span());
+        if self.is_next_token(TokenKind::SEMICOLON) {
+            self.next_token();
+        }
+        Ok(Statement::Throw(value, span))
+    }
+
+    // try {...} catch (e) {...} finally {...}, where either catch or finally can be omitted.
+    fn parse_try_expression(&mut self) -> Result<Expression, Errors> {
+        let start = self.current_token.span;
+        self.expect_next(TokenKind::LBRACE)?;
+        let body = self.parse_block_statements(TokenKind::LBRACE)?;
+        let mut end = body.span();
+        let mut catch = None;
+        if self.expect_next_token(TokenKind::CATCH) {
+            self.expect_next(TokenKind::LPAREN)?;
+            self.expect_next(TokenKind::IDENT)?;
+            let name = self.current_token.literal.clone();
+            self.expect_next(TokenKind::RPAREN)?;
+            self.expect_next(TokenKind::LBRACE)?;
+            let handler = self.parse_block_statements(TokenKind::LBRACE)?;
+            end = handler.span();
+            catch = Some((name, Box::new(handler)));
+        }
+        let mut finally = None;
+        if self.expect_next_token(TokenKind::FINALLY) {
+            self.expect_next(TokenKind::LBRACE)?;
+            let block = self.parse_block_statements(TokenKind::LBRACE)?;
+            end = block.span();
+            finally = Some(Box::new(block));
+        }
+        if catch.is_none() && finally.is_none() {
+            return Err(Errors::new(ErrorKind::TryWithoutHandler, start.to(end)))
+        }
+        Ok(Expression::Try{body: Box::new(body), catch, finally, span: start.to(end)})
     }
 
     fn parse_expression_statement(&mut self) -> Result<Statement, Errors> {
@@ -104,21 +371,31 @@ impl<'a>  Parser<'a>  {
     }
 
     fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression, Errors> {
+        self.nested(|parser| parser.parse_expression_inner(precedence))
+    }
+
+    fn parse_expression_inner(&mut self, precedence: Precedence) -> Result<Expression, Errors> {
+        let span = self.current_token.span;
         let mut exp = match self.current_token.token_type {
-            TokenKind::IDENT => {Expression::Identifier(self.parse_identifier()?)},
+            TokenKind::IDENT => {Expression::Identifier(self.parse_identifier()?, span)},
             TokenKind::STRING => {
-                Expression::String(self.parse_string()?)},
-            TokenKind::INT => Expression::Integer(self.parse_integer()?),
-            TokenKind::TRUE => Expression::Bool(true),
-            TokenKind::FALSE => Expression::Bool(false),
+                Expression::String(self.parse_string()?, span)},
+            TokenKind::TemplateHead => self.parse_interpolation()?,
+            TokenKind::INT => Expression::Integer(self.parse_integer()?, span),
+            TokenKind::FLOAT => Expression::Float(self.parse_float()?, span),
+            TokenKind::TRUE => Expression::Bool(true, span),
+            TokenKind::FALSE => Expression::Bool(false, span),
             TokenKind::IF =>   self.parse_if_expression()?,
+            TokenKind::MATCH => self.parse_match_expression()?,
+            TokenKind::TRY => self.parse_try_expression()?,
             TokenKind::LPAREN => self.parse_grouped_expression()?,
             TokenKind::LBRACE => self.parse_hash_literal()?,
             TokenKind::LBRACKET => self.parse_array_literal()?,
             TokenKind::FUNCTION => self.parse_function_expression()?,
             TokenKind::BANG => self.parse_prefix_expression()?,
             TokenKind::MINUS => self.parse_prefix_expression()?,
-            _ => return Err(Errors::TokenInvalid(self.current_token.clone()))
+            TokenKind::TILDE => self.parse_prefix_expression()?,
+            _ => return Err(Errors::token_invalid(&self.current_token))
         };
         while !self.is_next_token(TokenKind::SEMICOLON) && precedence < self.next_precedence() {
             //operator must be set in current position,
@@ -156,10 +433,59 @@ impl<'a>  Parser<'a>  {
                     self.next_token();
                     exp =  self.parse_infix_expression(exp)?;
                 },
+                TokenKind::PERCENT => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
+                TokenKind::POWER => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
+                TokenKind::AMPERSAND => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
+                TokenKind::PIPE => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
+                TokenKind::CARET => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
+                TokenKind::ShiftLeft => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
+                TokenKind::ShiftRight => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
+                TokenKind::LtEq => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
+                TokenKind::GtEq => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
+                TokenKind::AND => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
+                TokenKind::OR => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
                 TokenKind::LPAREN => {
                     self.next_token();
                     exp =  self.parse_call_arguments(exp)?;
                 },
+                TokenKind::ASSIGN | TokenKind::PlusAssign | TokenKind::MinusAssign |
+                TokenKind::AsteriskAssign | TokenKind::SlashAssign | TokenKind::PercentAssign => {
+                    self.next_token();
+                    exp =  self.parse_assign_expression(exp)?;
+                },
                 TokenKind::LBRACKET => {
                     self.next_token();
                     exp =  self.parse_index_expression(exp)?;
@@ -180,36 +506,91 @@ impl<'a>  Parser<'a>  {
         return Ok(self.current_token.literal.to_string())
     }
 
+    // "a ${b} c" is lexed as TemplateHead(a), b and TemplateTail(c),
+    // and a string with more than one expression has TemplateMiddles between them.
+    fn parse_interpolation(&mut self) -> Result<Expression, Errors> {
+        let start = self.current_token.span;
+        let mut parts = Vec::new();
+        loop {
+            // empty strings between expressions are left out.
+            if !self.current_token.literal.is_empty() {
+                parts.push(Expression::String(self.parse_string()?, self.current_token.span));
+            }
+            if self.current_token.token_type == TokenKind::TemplateTail {
+                break;
+            }
+            self.next_token();
+            parts.push(self.parse_expression(Precedence::LOWEST)?);
+            // the closing '}' is a part of the next string.
+            if !self.expect_next_token(TokenKind::TemplateMiddle) && !self.expect_next_token(TokenKind::TemplateTail) {
+                return Err(Errors::new(ErrorKind::UnexpectedToken{want: TokenKind::RBRACE, got: self.next_token.clone()},
+                                       self.next_token.span))
+            }
+        }
+        Ok(Expression::Interpolation{parts, span: start.to(self.current_token.span)})
+    }
+
+    // integers are decimal, or hexadecimal, octal and binary with 0x, 0o and 0b.
     fn parse_integer(&mut self) -> Result<i32, Errors> {
-        return Ok(self.current_token.literal.parse::<i32>().unwrap())
+        let value = self.parse_integer_magnitude()?;
+        if value > i32::MAX as i64 {
+            return Err(Errors::new(ErrorKind::IntegerOutOfRange(self.current_token.literal.clone()), self.current_token.span))
+        }
+        Ok(value as i32)
+    }
+
+    // the value of an integer literal before it is checked to fit in an integer.
+    fn parse_integer_magnitude(&self) -> Result<i64, Errors> {
+        let literal = &self.current_token.literal;
+        let (digits, radix) = match literal.get(..2) {
+            Some("0x") => (&literal[2..], 16),
+            Some("0o") => (&literal[2..], 8),
+            Some("0b") => (&literal[2..], 2),
+            _ => (literal.as_str(), 10)
+        };
+        let digits = digits.replace('_', "");
+        if digits.is_empty() || !digits.chars().all(|ch| ch.is_digit(radix)) {
+            return Err(Errors::new(ErrorKind::InvalidNumberLiteral(literal.clone()), self.current_token.span))
+        }
+        // every digit is valid, so the parse fails only when the value is too large even for i64.
+        match i64::from_str_radix(&digits, radix) {
+            Ok(value) => Ok(value),
+            Err(_) => Err(Errors::new(ErrorKind::IntegerOutOfRange(literal.clone()), self.current_token.span))
+        }
+    }
+
+    fn parse_float(&mut self) -> Result<Float, Errors> {
+        match self.current_token.literal.replace('_', "").parse::<f64>() {
+            Ok(value) => Ok(Float(value)),
+            Err(_) => Err(Errors::token_invalid(&self.current_token))
+        }
     }
+
     fn parse_hash_literal(&mut self) -> Result<Expression, Errors> {
+        let start = self.current_token.span;
         let mut pairs = BTreeMap::new();
 
         while !self.is_next_token(TokenKind::RBRACE) {
             self.next_token();
             let key = self.parse_expression(Precedence::LOWEST)?;
-            if !self.expect_next_token(TokenKind::COLON) {
-                return Ok(Expression::Null)
-            }
+            self.expect_next(TokenKind::COLON)?;
             self.next_token();
             let value = self.parse_expression(Precedence::LOWEST)?;
             // the values inside btree_map is alphabetically ordered.
             pairs.insert(Box::new(key), Box::new(value));
-            if !self.is_next_token(TokenKind::RBRACE) && !self.expect_next_token(TokenKind::COMMA) {
-                return Ok(Expression::Null)
+            if !self.is_next_token(TokenKind::RBRACE) {
+                self.expect_next(TokenKind::COMMA)?;
             }
         }
-        if !self.expect_next_token(TokenKind::RBRACE) {
-            return Ok(Expression::Null)
-        }
+        self.expect_next(TokenKind::RBRACE)?;
 
-        return Ok(Expression::Hashmap(pairs))
+        return Ok(Expression::Hashmap(pairs, start.to(self.current_token.span)))
     }
 
     fn parse_array_literal(&mut self) -> Result<Expression, Errors> {
+        let start = self.current_token.span;
         match self.parse_expression_list(TokenKind::RBRACKET)? {
-            list => Ok(Expression::Array(list)),
+            list => Ok(Expression::Array(list, start.to(self.current_token.span))),
             _ => Ok(Expression::Null)
         }
     }
@@ -223,103 +604,154 @@ impl<'a>  Parser<'a>  {
         } else {
             // skip left bracket;
             self.next_token();
-            list.push(self.parse_expression(Precedence::LOWEST)?);
+            list.push(self.parse_list_element()?);
 
             // fetch values inside list.
             while self.is_next_token(TokenKind::COMMA) {
                 self.next_token();
                 self.next_token();
-                list.push(self.parse_expression(Precedence::LOWEST)?)
-            }
-            if self.expect_next_token(end) {
-                Ok(list)
-            } else {
-                unimplemented!()
+                list.push(self.parse_list_element()?)
             }
+            self.expect_next(end)?;
+            Ok(list)
         }
     }
 
+    // an element of an array literal or an argument, which can be spread like ...array.
+    fn parse_list_element(&mut self) -> Result<Expression, Errors> {
+        if !self.is_current_token(TokenKind::ELLIPSIS) {
+            return self.parse_expression(Precedence::LOWEST)
+        }
+        let start = self.current_token.span;
+        self.next_token();
+        let value = self.parse_expression(Precedence::LOWEST)?;
+        let span = start.to(value.span());
+        Ok(Expression::Spread(Box::new(value), span))
+    }
+
     fn parse_index_expression(&mut self, left: Expression) -> Result<Expression, Errors> {
         self.next_token();
         let index = self.parse_expression(Precedence::LOWEST)?;
-        if !self.expect_next_token(TokenKind::RBRACKET) {
-            return Ok(Expression::Null)
-        }
-        Ok(Expression::IndexExpression{array: Box::new(left), subscript: Box::new(index)})
+        self.expect_next(TokenKind::RBRACKET)?;
+        let span = left.span().to(self.current_token.span);
+        Ok(Expression::IndexExpression{array: Box::new(left), subscript: Box::new(index), span})
     }
 
     fn parse_grouped_expression(&mut self) -> Result<Expression, Errors> {
         self.next_token();
         let lparen = self.parse_expression(Precedence::LOWEST)?;
-        if self.expect_next_token(TokenKind::RPAREN) {
-             return Ok(lparen)
-        }  else {
-            Err(Errors::TokenInvalid(self.current_token.clone()))
-        }
+        self.expect_next(TokenKind::RPAREN)?;
+        Ok(lparen)
 }
 
     fn parse_if_expression(&mut self) ->  Result<Expression, Errors> {
-        if !self.is_next_token(TokenKind::LPAREN) {
-            return Ok(Expression::Null)
-        }
-        self.next_token();
-        let condition = self.parse_expression(Precedence::LOWEST);
+        let start = self.current_token.span;
+        self.expect_next(TokenKind::LPAREN)?;
+        let condition = self.parse_expression(Precedence::LOWEST)?;
 
-        if !self.expect_next_token(TokenKind::LBRACE) {
-            return Ok(Expression::Null)
-            }
+        self.expect_next(TokenKind::LBRACE)?;
+        let consequence = Box::new(self.parse_block_statements(TokenKind::LBRACE)?);
+        let alternative = self.alternative()?;
         let expression = Expression::IfExpression{
-                            condition: Box::new(condition?),
-                            consequence: Box::new(self.parse_block_statements(TokenKind::LBRACE)?),
-                            alternative: self.alternative()?,
+                            condition: Box::new(condition),
+                            consequence: consequence,
+                            alternative: alternative,
+                            span: start.to(self.current_token.span),
                                                   };
         Ok(expression)
     }
 
+    fn parse_match_expression(&mut self) -> Result<Expression, Errors> {
+        let start = self.current_token.span;
+        self.expect_next(TokenKind::LPAREN)?;
+        let value = self.parse_expression(Precedence::LOWEST)?;
+        self.expect_next(TokenKind::LBRACE)?;
+        let mut arms = vec![];
+        while !self.is_next_token(TokenKind::RBRACE) {
+            self.next_token();
+            let pattern = self.parse_pattern()?;
+            let mut guard = None;
+            if self.expect_next_token(TokenKind::IF) {
+                self.next_token();
+                guard = Some(self.parse_expression(Precedence::LOWEST)?);
+            }
+            self.expect_next(TokenKind::FatArrow)?;
+            self.next_token();
+            let body = self.parse_expression(Precedence::LOWEST)?;
+            arms.push(MatchArm{pattern, guard, body});
+            if !self.is_next_token(TokenKind::RBRACE) {
+                self.expect_next(TokenKind::COMMA)?;
+            }
+        }
+        self.expect_next(TokenKind::RBRACE)?;
+        Ok(Expression::Match{value: Box::new(value), arms, span: start.to(self.current_token.span)})
+    }
+
     fn parse_block_statements(&mut self, token_kind: TokenKind) -> Result<Statement, Errors> {
+        self.nested(|parser| parser.parse_block_statements_inner(token_kind))
+    }
+
+    fn parse_block_statements_inner(&mut self, token_kind: TokenKind) -> Result<Statement, Errors> {
+        let start = self.current_token.span;
         self.next_token();
         let mut statements: Vec<Statement> = vec![];
         while !self.is_current_token(TokenKind::RBRACE) && !self.is_current_token(TokenKind::EOF) {
-            let statement = self.parse_statement()?;
-            statements.push(statement);
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                // the rest of the block can be parsed even if a statement in it is broken.
+                Err(error) => {
+                    self.record_error(error);
+                    self.synchronize();
+                    continue;
+                }
+            }
             self.next_token();
         }
-        Ok(Statement::Block(statements))
+        if !self.is_current_token(TokenKind::RBRACE) {
+            return Err(Errors::new(ErrorKind::UnexpectedToken{want: TokenKind::RBRACE, got: self.current_token.clone()},
+                                   self.current_token.span))
+        }
+        Ok(Statement::Block(statements, start.to(self.current_token.span)))
     }
 
     fn alternative(&mut self) -> Result<Option<Box<Statement>>, Errors> {
         if self.is_next_token(TokenKind::ELSE) {
         self.next_token();
-        if self.expect_next_token(TokenKind::LBRACE) {
-            let alternative = self.parse_block_statements(TokenKind::LBRACE)?;
-            Ok(Some(Box::new(alternative)))
-        }else {
-            return Err(Errors::TokenInvalid(self.current_token.clone()))
+        // "else if" has the next if expression as its alternative.
+        if self.expect_next_token(TokenKind::IF) {
+            let expression = self.parse_if_expression()?;
+            return Ok(Some(Box::new(Statement::ExpressionStatement(expression))))
         }
+        self.expect_next(TokenKind::LBRACE)?;
+        let alternative = self.parse_block_statements(TokenKind::LBRACE)?;
+        Ok(Some(Box::new(alternative)))
     } else {
            Ok(None)
         }
     }
 
     fn parse_function_expression(&mut self) -> Result<Expression, Errors> {
-        if self.expect_next_token(TokenKind::LPAREN) {
-            println!("TokenKind should be LPAREN but actually is {:?}",self.next_token.token_type)            
-        }
+        let start = self.current_token.span;
+        self.expect_next(TokenKind::LPAREN)?;
         let parameters = self.parse_function_parameters()?;
-        if self.expect_next_token(TokenKind::LBRACE) {
-            println!("TokenKind should be LBRACE but actually is {:?}",self.next_token.token_type)            
-        }        
+        self.expect_next(TokenKind::LBRACE)?;
 
-        let body = self.parse_block_statements(TokenKind::LBRACE)?;
+        // break and continue in a function can't leave loops outside of it.
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let body = self.parse_block_statements(TokenKind::LBRACE);
+        self.loop_depth = loop_depth;
+        let body = body?;
         let expression = Expression::FunctionLiteral{
-            parameters: parameters,
-            body: Box::new(body)
+            name: None,
+            parameters: Rc::new(parameters),
+            span: start.to(body.span()),
+            body: Rc::new(body)
         };
         Ok(expression)
     }
 
-    fn parse_function_parameters(&mut self) -> Result<Vec<Expression>, Errors> {
-        let mut identifiers = vec![];
+    fn parse_function_parameters(&mut self) -> Result<Vec<Parameter>, Errors> {
+        let mut identifiers: Vec<Parameter> = vec![];
         // if next_token is ")", there are no parameters 
         if self.is_next_token(TokenKind::RPAREN) {
             self.next_token();
@@ -328,45 +760,92 @@ impl<'a>  Parser<'a>  {
         // if function has one or more parameters
         // skip "(" and push these into list.
         self.next_token();
-        identifiers.push(Expression::Identifier(self.current_token.literal.clone()));
+        identifiers.push(self.parse_parameter()?);
         while self.is_next_token(TokenKind::COMMA) {
             self.next_token();
             self.next_token();
-        identifiers.push(Expression::Identifier(self.current_token.literal.clone()));
-        }
-        if !self.expect_next_token(TokenKind::RPAREN) {
-            panic!()
+            // nothing can follow the rest parameter.
+            if let Some(last) = identifiers.last().filter(|last| last.rest) {
+                return Err(Errors::new(ErrorKind::RestParameterNotLast(last.name.clone()), self.current_token.span))
+            }
+        identifiers.push(self.parse_parameter()?);
         }
+        self.expect_next(TokenKind::RPAREN)?;
         Ok(identifiers)
     }
 
+    // x, x = default or ...x
+    fn parse_parameter(&mut self) -> Result<Parameter, Errors> {
+        let rest = self.is_current_token(TokenKind::ELLIPSIS);
+        if rest {
+            self.next_token();
+        }
+        if !self.is_current_token(TokenKind::IDENT) {
+            return Err(Errors::token_invalid(&self.current_token))
+        }
+        let name = self.current_token.literal.clone();
+        let mut default = None;
+        if !rest && self.expect_next_token(TokenKind::ASSIGN) {
+            self.next_token();
+            default = Some(self.parse_expression(Precedence::LOWEST)?);
+        }
+        Ok(Parameter{name, default, rest})
+    }
+
     fn parse_call_arguments(&mut self, func: Expression) -> Result<Expression, Errors> {
         let mut arguments = vec![];
 
         if self.is_next_token(TokenKind::RPAREN) {
             self.next_token();
-            return Ok(Expression::CallExpression{function: Box::new(func), body: arguments})
+            let span = func.span().to(self.current_token.span);
+            return Ok(Expression::CallExpression{function: Box::new(func), body: arguments, span})
         } else {
         self.next_token();
-        arguments.push(self.parse_expression(Precedence::LOWEST)?);
+        arguments.push(self.parse_argument()?);
         while self.is_next_token(TokenKind::COMMA) {
             self.next_token();
             self.next_token();
-            arguments.push(self.parse_expression(Precedence::LOWEST)?);
+            let argument = self.parse_argument()?;
+            // keyword arguments come after all positional ones.
+            if let (Some(Expression::Keyword{..}), false) = (arguments.last(), matches!(argument, Expression::Keyword{..})) {
+                return Err(Errors::new(ErrorKind::PositionalAfterKeyword, argument.span()))
+            }
+            arguments.push(argument);
         }
-        if !self.expect_next_token(TokenKind::RPAREN) {
-            return Ok(Expression::Null)
-                }
+        self.expect_next(TokenKind::RPAREN)?;
             }
-        Ok(Expression::CallExpression{function: Box::new(func), body: arguments})
+        let span = func.span().to(self.current_token.span);
+        Ok(Expression::CallExpression{function: Box::new(func), body: arguments, span})
+    }
+
+    // an argument is a list element or a keyword one, name: value.
+    fn parse_argument(&mut self) -> Result<Expression, Errors> {
+        if !(self.is_current_token(TokenKind::IDENT) && self.is_next_token(TokenKind::COLON)) {
+            return self.parse_list_element()
+        }
+        let start = self.current_token.span;
+        let name = self.current_token.literal.clone();
+        self.next_token();
+        self.next_token();
+        let value = self.parse_expression(Precedence::LOWEST)?;
+        let span = start.to(value.span());
+        Ok(Expression::Keyword{name, value: Box::new(value), span})
     }
 
     fn parse_prefix_expression(&mut self) -> Result<Expression, Errors> {
+        let start = self.current_token.span;
         let current_token = self.current_token.literal.to_string();
         self.next_token();
+        // 2147483648 is too large for an integer, but -2147483648 is the smallest one.
+        // ** binds the literal before the sign, so the sign isn't applied to it first.
+        if current_token == "-" && self.is_current_token(TokenKind::INT) && self.next_precedence() <= Precedence::PREFIX
+            && self.parse_integer_magnitude()? == -(i32::MIN as i64) {
+            return Ok(Expression::Integer(i32::MIN, start.to(self.current_token.span)))
+        }
         let right = self.parse_expression(Precedence::PREFIX)?;
         let expression = Expression::PrefixExpression{
                                            operator: current_token,
+                                           span: start.to(right.span()),
                                            right_expression: Box::new(right)
                                         };
         return Ok(expression)
@@ -378,18 +857,34 @@ impl<'a>  Parser<'a>  {
             TokenKind::MINUS => "-".to_string(),
             TokenKind::ASTERISK => "*".to_string(),
             TokenKind::SLASH => "/".to_string(),
+            TokenKind::PERCENT => "%".to_string(),
+            TokenKind::POWER => "**".to_string(),
+            TokenKind::AMPERSAND => "&".to_string(),
+            TokenKind::PIPE => "|".to_string(),
+            TokenKind::CARET => "^".to_string(),
+            TokenKind::ShiftLeft => "<<".to_string(),
+            TokenKind::ShiftRight => ">>".to_string(),
             TokenKind::EQ => "==".to_string(),
             TokenKind::NotEq => "!=".to_string(),
             TokenKind::LT => "<".to_string(),
             TokenKind::GT => ">".to_string(),
-            _ => {panic!()}
+            TokenKind::LtEq => "<=".to_string(),
+            TokenKind::GtEq => ">=".to_string(),
+            TokenKind::AND => "&&".to_string(),
+            TokenKind::OR => "||".to_string(),
+            _ => return Err(Errors::token_invalid(&self.current_token))
         };
         // current token will be read in parse_expression().
         // next token must be implemented in order that next operator is set to current_token
-        let precedence = self.current_precedence();
+        let precedence = match self.current_token.token_type {
+            // ** is right associative, so 2 ** 3 ** 2 is 2 ** (3 ** 2).
+            TokenKind::POWER => Precedence::PREFIX,
+            _ => self.current_precedence()
+        };
         self.next_token();
         let right = self.parse_expression(precedence)?;
         let infix_expression = Expression::InfixExpression{
+                                    span: left.span().to(right.span()),
                                     left_expression: Box::new(left),
                                     operator: operator,
                                     right_expression: Box::new(right)
@@ -397,6 +892,24 @@ impl<'a>  Parser<'a>  {
         return Ok(infix_expression)
     }
 
+    // only a variable or an element of an array or a hash can be assigned.
+    fn parse_assign_expression(&mut self, target: Expression) -> Result<Expression, Errors> {
+        match target {
+            Expression::Identifier(..) | Expression::IndexExpression{..} => {},
+            _ => return Err(Errors::new(ErrorKind::InvalidAssignTarget(format!("{}", target)), target.span()))
+        }
+        let operator = self.current_token.literal.clone();
+        self.next_token();
+        // assignment is right associative, so a = b = 1 assigns 1 to both.
+        let value = self.parse_expression(Precedence::LOWEST)?;
+        Ok(Expression::Assign{
+            span: target.span().to(value.span()),
+            target: Box::new(target),
+            operator,
+            value: Box::new(value)
+        })
+    }
+
     fn current_precedence(&mut self) -> Precedence {
         return self.current_token.get_precedence()
     }
@@ -421,6 +934,16 @@ impl<'a>  Parser<'a>  {
             return false
         }
     }
+
+    // same as expect_next_token(), but the unexpected token is returned as an error.
+    fn expect_next(&mut self, token_kind: TokenKind) -> Result<(), Errors> {
+        if self.expect_next_token(token_kind) {
+            Ok(())
+        } else {
+            Err(Errors::new(ErrorKind::UnexpectedToken{want: token_kind, got: self.next_token.clone()},
+                            self.next_token.span))
+        }
+    }
 }
 
 // if cfg(test) is written, test code is compiled only when test runs
@@ -428,8 +951,15 @@ impl<'a>  Parser<'a>  {
 mod testing {
     use crate::lexer::Lexer;
     use crate::parser::Parser;
+    use crate::ast::{Program, Statement};
     use std::str::FromStr;
 
+    // the program of the input which has no syntax errors.
+    fn parse_without_errors(parser: &mut Parser) -> Program {
+        let (program, errors) = parser.parse_program();
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        program
+    }
 
     #[test]
     fn test_let_statement() {
@@ -439,7 +969,7 @@ mod testing {
         
         let lexer = Lexer::new(&input);
         let mut parser = Parser::new(lexer);
-        let program = parser.parse_program().unwrap();
+        let program = parse_without_errors(&mut parser);
         let stmt = format!("{}", &program.statements[0]);
         assert_eq!(program.statements.len(), 3);
     }
@@ -452,7 +982,7 @@ mod testing {
         
         let lexer = Lexer::new(&input);
         let mut parser = Parser::new(lexer);
-        let program = parser.parse_program().unwrap();
+        let program = parse_without_errors(&mut parser);
 
         assert_eq!(program.statements.len(), 3);
         let tests = vec![
@@ -472,7 +1002,7 @@ mod testing {
         
         let lexer = Lexer::new(&input);
         let mut parser = Parser::new(lexer);
-        let program = parser.parse_program().unwrap();
+        let program = parse_without_errors(&mut parser);
         assert_eq!(program.statements.len(), 1); // 識別子が一つであること
         let stmt = format!("{}", program.statements[0]);
         assert_eq!(stmt, "foobar");
@@ -484,7 +1014,7 @@ mod testing {
             
             let lexer = Lexer::new(&input);
             let mut parser = Parser::new(lexer);
-            let program = parser.parse_program().unwrap();
+            let program = parse_without_errors(&mut parser);
             assert_eq!(program.statements.len(), 1); // confirm the number of statements is 1.
             let stmt = format!("{}", program.statements[0]);
             assert_eq!(stmt, "5".to_string());
@@ -498,7 +1028,7 @@ mod testing {
             for test in prefix_tests.iter() {
                 let lexer = Lexer::new(test);
                 let mut parser = Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
+                let program = parse_without_errors(&mut parser);
                 assert_eq!(program.statements.len(), 1); // confirm the number of statements is 1.
                 let statements = format!("{}", program.statements[0]);
                 for (i, statement) in prefix_tests.iter().enumerate() {
@@ -516,7 +1046,7 @@ mod testing {
                 for test in infix_tests.iter() {
                     let lexer = Lexer::new(test);
                     let mut parser = Parser::new(lexer);
-                    let program = parser.parse_program().unwrap();
+                    let program = parse_without_errors(&mut parser);
                     assert_eq!(program.statements.len(), 1); // confirm the number of statements is 1.
                     let statements = format!("{}", program.statements[0]);
                     for (i, statement) in infix_tests.iter().enumerate() {
@@ -541,13 +1071,24 @@ mod testing {
                                         ("(-(5 + 5))", "-5 + 5"),
                                         ("(!(true == true))", "!true == true"),
                                         ("((a * ([1, 2, 3, 4][(b * c)])) * d)", "a * [1, 2, 3, 4][b * c] * d"),
+                                        ("((a <= (b + 1)) == (c >= d))", "a <= b + 1 == c >= d"),
+                                        ("((a || (b && c)) || d)", "a || b && c || d"),
+                                        ("(((a < b) && (b != c)) || (!d))", "a < b && b != c || !d"),
+                                        ("((a + (b % c)) - d)", "a + b % c - d"),
+                                        ("(-(2 ** 2))", "-2 ** 2"),
+                                        ("(2 ** (3 ** 2))", "2 ** 3 ** 2"),
+                                        ("((a * (b ** c)) * d)", "a * b ** c * d"),
+                                        ("(2 ** (-1))", "2 ** -1"),
+                                        ("((a | (b ^ (c & d))) == e)", "a | b ^ c & d == e"),
+                                        ("((1 << (a + 1)) & (b >> 2))", "1 << a + 1 & b >> 2"),
+                                        ("((~a) & b)", "~a & b"),
                                         ];
                 // compare the result of parseing the first element of tuple
                 // with second, third elements.
                 for (i, test) in infix_tests.iter().enumerate() {
                     let lexer = Lexer::new(test.0);
                     let mut parser = Parser::new(lexer);
-                    let program = parser.parse_program().unwrap();
+                    let program = parse_without_errors(&mut parser);
                     let statements = format!("{}", program.statements[0]);
                     assert_eq!(statements, test.1);
                 }
@@ -565,7 +1106,7 @@ mod testing {
                     for (test, right) in bool_tests.iter() {
                         let lexer = Lexer::new(test);
                         let mut parser = Parser::new(lexer);
-                        let program = parser.parse_program().unwrap();
+                        let program = parse_without_errors(&mut parser);
                         assert_eq!(program.statements.len(), 1); // confirm the number of statements is 1.
                         let statements = format!("{}", program.statements[0]);
                         assert_eq!(FromStr::from_str(&statements.to_string()[..]), Ok(*right));
@@ -591,18 +1132,39 @@ mod testing {
                 let input = "if (1 > 2) {10} else {20}".to_string();
                 let lexer = Lexer::new(&input);
                 let mut parser = Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
+                let program = parse_without_errors(&mut parser);
                 let statements = format!("{}", program.statements[0]);
                 println!("{:?}", statements);
                 assert_eq!(input, statements);
                 }
 
+            #[test]
+            fn test_else_if_expression() {
+                let input = "if (x < 0) {-1} else if (x == 0) {0} else if (x < 10) {1} else {2}".to_string();
+                let lexer = Lexer::new(&input);
+                let mut parser = Parser::new(lexer);
+                let program = parse_without_errors(&mut parser);
+                assert_eq!(format!("{}", program.statements[0]), input);
+
+                let tests = vec![
+                    ("if (a) {1} else if {2}", "expected LPAREN but got {"),
+                    ("if (a) {1} else if (b) 2", "expected LBRACE but got 2"),
+                    ("if (a) {1} else 2", "expected LBRACE but got 2"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let errors = parser.parse_program().1;
+                    assert_eq!(format!("{}", errors[0]), *expected);
+                    }
+                }
+
             #[test]
             fn test_function_expression() {
                 let input = "fn (x, y) {x + y}".to_string();
                 let lexer = Lexer::new(&input);
                 let mut parser = Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
+                let program = parse_without_errors(&mut parser);
                 let statements = format!("{}", program.statements[0]);
                 assert_eq!(input, statements);
                 }
@@ -612,7 +1174,7 @@ mod testing {
                 let input = "add(1, 2 * 3, 4 + 5);".to_string();
                 let lexer = Lexer::new(&input);
                 let mut parser = Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
+                let program = parse_without_errors(&mut parser);
                 let statements = format!("{}", program.statements[0]);
                 assert_eq!(input, statements);
                 }
@@ -621,7 +1183,7 @@ mod testing {
                 let input = r#""Hello world;""#;
                 let lexer = Lexer::new(&input);
                 let mut parser = Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
+                let program = parse_without_errors(&mut parser);
                 let statements = format!("{}", program.statements[0]);
                 assert_eq!("Hello world;", statements);
                 }
@@ -631,7 +1193,7 @@ mod testing {
                 let input = "[1, 2 * 2, 3 + 3]";
                 let lexer = Lexer::new(&input);
                 let mut parser = Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
+                let program = parse_without_errors(&mut parser);
                 let statements = format!("{}", program.statements[0]);
                 assert_eq!("[1, 2 * 2, 3 + 3]", statements);
                 }
@@ -640,7 +1202,7 @@ mod testing {
                 let input = "my_array[1 + 1]";
                 let lexer = Lexer::new(&input);
                 let mut parser = Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
+                let program = parse_without_errors(&mut parser);
                 let statements = format!("{}", program.statements[0]);
                 assert_eq!("my_array[1 + 1]", statements);
                 }
@@ -649,7 +1211,7 @@ mod testing {
                 let input = "{\"a\": 4, \"b\": 1, \"c\": 3, \"d\": 2}";
                 let lexer = Lexer::new(&input);
                 let mut parser = Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
+                let program = parse_without_errors(&mut parser);
                 let statements = format!("{}", program.statements[0]);
                 assert_eq!("{a: 4, b: 1, c: 3, d: 2}", statements);
                 }
@@ -658,7 +1220,7 @@ mod testing {
                 let input = "{}";
                 let lexer = Lexer::new(&input);
                 let mut parser = Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
+                let program = parse_without_errors(&mut parser);
                 let statements = format!("{}", program.statements[0]);
                 assert_eq!("{}", statements);
                 }
@@ -667,8 +1229,357 @@ mod testing {
                 let input = "{\"one\": 0 + 1, \"two\": 10-8, \"three\": 15 / 5}";
                 let lexer = Lexer::new(&input);
                 let mut parser = Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
+                let program = parse_without_errors(&mut parser);
                 let statements = format!("{}", program.statements[0]);
                 assert_eq!("{one: 0 + 1, three: 15 / 5, two: 10 - 8}", statements);
                     }
-            }
\ No newline at end of file
+
+            #[test]
+            fn test_expression_spans() {
+                let input = "let x = 1;\nlet y = add(x,\n  2 * 3);";
+                let lexer = Lexer::new(&input);
+                let mut parser = Parser::new(lexer);
+                let program = parse_without_errors(&mut parser);
+                let span = program.statements[1].span();
+                assert_eq!((span.line, span.column, span.start, span.end), (2, 1, 11, 35));
+                if let Statement::LetStatement{value, ..} = &program.statements[1] {
+                    let span = value.span();
+                    assert_eq!((span.line, span.column, &input[span.start..span.end]), (2, 9, "add(x,\n  2 * 3)"));
+                } else {
+                    panic!("let statement is expected");
+                }
+                }
+
+            #[test]
+            fn test_error_position() {
+                let input = "let a = 1;\n  let = 5;";
+                let lexer = Lexer::new(&input);
+                let mut parser = Parser::new(lexer);
+                let errors = parser.parse_program().1;
+                assert_eq!(errors[0].report("script.mk"), "script.mk:2:7: invalid token: =");
+                }
+
+            #[test]
+            fn test_error_recovery() {
+                let input = r#"let a = 1;
+                               let = 5;
+                               let b = {"x" 1};
+                               let f = fn(x) { let y = ; x };
+                               let c = a + 2;
+                               if (a { 1 };
+                               print(c"#;
+                let lexer = Lexer::new(&input);
+                let mut parser = Parser::new(lexer);
+                let (program, errors) = parser.parse_program();
+                let errors = errors.iter().map(|error| error.report("script.mk")).collect::<Vec<_>>();
+                assert_eq!(errors, vec![
+                    "script.mk:2:36: invalid token: =",
+                    "script.mk:3:45: expected COLON but got 1",
+                    "script.mk:4:56: invalid token: ;",
+                    "script.mk:6:38: expected RPAREN but got {",
+                    "script.mk:7:39: expected RPAREN but got end of input",
+                    ]);
+                let statements = program.statements.iter().map(|stmt| format!("{}", stmt)).collect::<Vec<_>>();
+                assert_eq!(statements, vec!["let a = 1;", "let f = fn (x) {x};", "let c = a + 2;"]);
+                }
+
+            #[test]
+            fn test_parse_program_returns_all_errors() {
+                let tests = vec![
+                    ("if x { 1 }", vec!["expected LPAREN but got x"]),
+                    ("fn x { 1 }", vec!["expected LPAREN but got {"]),
+                    ("let f = fn x() { 1 }", vec!["expected LPAREN but got x"]),
+                    ("fn (x) 1", vec!["expected LBRACE but got 1"]),
+                    ("add(1, 2", vec!["expected RPAREN but got end of input"]),
+                    ("a[1; b[2]", vec!["expected RBRACKET but got ;"]),
+                    ("if (true) { 1", vec!["expected RBRACE but got end of input"]),
+                    ("(1 + 2; ) + 3; 4 +", vec!["expected RPAREN but got ;", "invalid token: )", "unexpected end of input"]),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let errors = parser.parse_program().1;
+                    let messages = errors.iter().map(|error| format!("{}", error)).collect::<Vec<_>>();
+                    assert_eq!(&messages, expected);
+                    }
+                }
+
+            #[test]
+            fn test_integer_literal_syntax() {
+                let tests = vec![
+                    ("0xff", "255"),
+                    ("0xFF_FF", "65535"),
+                    ("0o17", "15"),
+                    ("0b1010_1010", "170"),
+                    ("1_000_000", "1000000"),
+                    ("0x7fffffff", "2147483647"),
+                    ("1_000.5", "1000.5"),
+                    // the smallest integer can be written only with its sign.
+                    ("-2147483648", "-2147483648"),
+                    ("-0x8000_0000", "-2147483648"),
+                    ("-2147483647", "-2147483647"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let program = parse_without_errors(&mut parser);
+                    assert_eq!(format!("{}", program.statements[0]), *expected);
+                    }
+
+                let tests = vec![
+                    ("0x80000000", "1:1: integer literal is out of range: 0x80000000"),
+                    ("0b1_0000_0000_0000_0000_0000_0000_0000_0000", "1:1: integer literal is out of range: 0b1_0000_0000_0000_0000_0000_0000_0000_0000"),
+                    ("let x = 0xg;", "1:9: invalid number literal: 0xg"),
+                    ("0b102", "1:1: invalid number literal: 0b102"),
+                    ("0o8", "1:1: invalid number literal: 0o8"),
+                    ("0x", "1:1: invalid number literal: 0x"),
+                    ("99999999999999999999", "1:1: integer literal is out of range: 99999999999999999999"),
+                    ("-2147483649", "1:2: integer literal is out of range: 2147483649"),
+                    // ** is applied before the sign, so the literal is still out of range.
+                    ("-2147483648 ** 1", "1:2: integer literal is out of range: 2147483648"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let errors = parser.parse_program().1;
+                    assert_eq!(format!("{}: {}", errors[0].span, errors[0]), *expected);
+                    }
+                }
+            
+            #[test]
+            fn test_interpolation() {
+                let input = r#""${a} + ${b} = ${a + b}""#;
+                let lexer = Lexer::new(&input);
+                let mut parser = Parser::new(lexer);
+                let program = parse_without_errors(&mut parser);
+                assert_eq!(format!("{}", program.statements[0]), "${a} + ${b} = ${a + b}");
+
+                let tests = vec![
+                    (r#""${}""#, "invalid token: "),
+                    (r#""${a b}""#, "expected RBRACE but got b"),
+                    (r#""${a"#, "expected RBRACE but got end of input"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let errors = parser.parse_program().1;
+                    assert_eq!(format!("{}", errors[0]), *expected);
+                    }
+                }
+            
+            #[test]
+            fn test_loop_statements() {
+                let tests = vec![
+                    ("while (i < 10) { let i = i + 1; }", "while (i < 10) {let i = i + 1;}"),
+                    ("for (x in [1, 2]) { print(x) }", "for (x in [1, 2]) {print(x);}"),
+                    ("for (k, v in h) { if (v) { continue; } else { break } }", "for (k, v in h) {if (v) {continue} else {break}}"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let program = parse_without_errors(&mut parser);
+                    assert_eq!(format!("{}", program.statements[0]), *expected);
+                    }
+
+                let tests = vec![
+                    ("break;", "1:1: 'break' outside of a loop"),
+                    ("while (true) { let f = fn() { continue }; }", "1:31: 'continue' outside of a loop"),
+                    ("for (x of xs) {}", "1:8: expected IN but got of"),
+                    ("for (1 in xs) {}", "1:6: expected IDENT but got 1"),
+                    ("while true {}", "1:7: expected LPAREN but got true"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let errors = parser.parse_program().1;
+                    assert_eq!(format!("{}: {}", errors[0].span, errors[0]), *expected);
+                    }
+                }
+            
+            #[test]
+            fn test_assign_expression() {
+                let tests = vec![
+                    ("x = 1 + 2", "x = 1 + 2"),
+                    ("a = b = c", "a = b = c"),
+                    ("x += y * 2", "x += y * 2"),
+                    ("arr[i -= 1] %= 3", "arr[i -= 1] %= 3"),
+                    ("h[\"k\"] = x || y", "h[k] = x || y"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let program = parse_without_errors(&mut parser);
+                    assert_eq!(format!("{}", program.statements[0]), *expected);
+                    }
+
+                let tests = vec![
+                    ("1 = 2", "cannot assign to 1"),
+                    ("x + y = 2", "cannot assign to x + y"),
+                    ("f() = 2", "cannot assign to f();"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let errors = parser.parse_program().1;
+                    assert_eq!(format!("{}", errors[0]), *expected);
+                    }
+                }
+            
+            #[test]
+            fn test_function_statement() {
+                let input = "fn add(x, y) { x + y }";
+                let lexer = Lexer::new(&input);
+                let mut parser = Parser::new(lexer);
+                let program = parse_without_errors(&mut parser);
+                assert_eq!(format!("{}", program.statements[0]), "fn add(x, y) {x + y}");
+                let span = program.statements[0].span();
+                assert_eq!(&input[span.start..span.end], input);
+                }
+            
+            #[test]
+            fn test_destructuring_let() {
+                let tests = vec![
+                    ("let [a, b] = pair;", "let [a, b] = pair;"),
+                    ("let [first, ...rest] = xs", "let [first, ...rest] = xs;"),
+                    ("let [...all] = xs", "let [...all] = xs;"),
+                    ("let [] = xs", "let [] = xs;"),
+                    (r#"let {"name": n, "age": a} = person;"#, "let {name: n, age: a} = person;"),
+                    (r#"let {"point": [x, y], 1: {"z": z}} = h;"#, "let {point: [x, y], 1: {z: z}} = h;"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let program = parse_without_errors(&mut parser);
+                    assert_eq!(format!("{}", program.statements[0]), *expected);
+                    }
+
+                let tests = vec![
+                    ("let [a, ...rest, b] = xs", "1:16: expected RBRACKET but got ,"),
+                    ("let [a b] = xs", "1:8: expected COMMA but got b"),
+                    ("let [+] = xs", "1:6: invalid token: +"),
+                    (r#"let {"a"} = h"#, "1:9: expected COLON but got }"),
+                    ("let [a] xs", "1:9: invalid token: xs"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let errors = parser.parse_program().1;
+                    assert_eq!(format!("{}: {}", errors[0].span, errors[0]), *expected);
+                    }
+                }
+            
+            #[test]
+            fn test_match_expression() {
+                let tests = vec![
+                    (r#"match (x) { 1 => "one", -2 => "minus two", _ => "other" }"#, "match (x) {1 => one, -2 => minus two, _ => other}"),
+                    ("match (xs) { [] => 0, [x, ...rest] if x > 0 => x + 1, }", "match (xs) {[] => 0, [x, ...rest] if x > 0 => x + 1}"),
+                    (r#"match (h) { {"k": [a, _]} => a, n => n }"#, "match (h) {{k: [a, _]} => a, n => n}"),
+                    ("let y = match (x) { true => 1, false => 0 };", "let y = match (x) {true => 1, false => 0};"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let program = parse_without_errors(&mut parser);
+                    assert_eq!(format!("{}", program.statements[0]), *expected);
+                    }
+
+                let tests = vec![
+                    ("match x { _ => 1 }", "1:7: expected LPAREN but got x"),
+                    ("match (x) { 1 + 2 => 3 }", "1:15: expected FatArrow but got +"),
+                    ("match (x) { 1 => 1 2 => 2 }", "1:20: expected COMMA but got 2"),
+                    ("match (x) { 1 = 1 }", "1:15: expected FatArrow but got ="),
+                    ("match (x) { _ => 1", "1:19: expected COMMA but got end of input"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let errors = parser.parse_program().1;
+                    assert_eq!(format!("{}: {}", errors[0].span, errors[0]), *expected);
+                    }
+                }
+            
+            #[test]
+            fn test_exceptions() {
+                let tests = vec![
+                    ("try { f() } catch (e) { e }", "try {f();} catch (e) {e}"),
+                    ("try { f() } finally { g() }", "try {f();} finally {g();}"),
+                    ("let x = try { 1 } catch (err) { 2 } finally { 3 };", "let x = try {1} catch (err) {2} finally {3};"),
+                    (r#"throw "boom";"#, "throw boom"),
+                    ("throw {\"code\": 1 + 2}", "throw {code: 1 + 2}"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let program = parse_without_errors(&mut parser);
+                    assert_eq!(format!("{}", program.statements[0]), *expected);
+                    }
+
+                let tests = vec![
+                    ("try { 1 }", "1:1: try needs catch or finally"),
+                    ("try 1 catch (e) { 2 }", "1:5: expected LBRACE but got 1"),
+                    ("try { 1 } catch e { 2 }", "1:17: expected LPAREN but got e"),
+                    ("try { 1 } catch (1) { 2 }", "1:18: expected IDENT but got 1"),
+                    ("try { 1 } finally (e) { 2 }", "1:19: expected LBRACE but got ("),
+                    ("throw", "1:6: unexpected end of input"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let errors = parser.parse_program().1;
+                    assert_eq!(format!("{}: {}", errors[0].span, errors[0]), *expected);
+                    }
+                }
+            
+            #[test]
+            fn test_parameters_and_spread() {
+                let tests = vec![
+                    ("fn(x, y = 2, ...rest) { x }", "fn (x, y = 2, ...rest) {x}"),
+                    ("fn f(a, b = a * 2) { b }", "fn f(a, b = a * 2) {b}"),
+                    ("f(1, ...xs, 2)", "f(1, ...xs, 2);"),
+                    ("[0, ...[1, 2]]", "[0, ...[1, 2]]"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let program = parse_without_errors(&mut parser);
+                    assert_eq!(format!("{}", program.statements[0]), *expected);
+                    }
+
+                let tests = vec![
+                    ("fn(...xs, y) { y }", "1:11: rest parameter must be the last one: xs"),
+                    ("fn(...xs = 1) { xs }", "1:10: expected RPAREN but got ="),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let errors = parser.parse_program().1;
+                    assert_eq!(format!("{}: {}", errors[0].span, errors[0]), *expected);
+                    }
+                }
+            
+            #[test]
+            fn test_keyword_arguments() {
+                let input = "rect(10, height: h * 2, ...rest)";
+                let lexer = Lexer::new(&input);
+                let mut parser = Parser::new(lexer);
+                let errors = parser.parse_program().1;
+                assert_eq!(format!("{}: {}", errors[0].span, errors[0]), "1:25: positional argument follows keyword argument");
+
+                let input = "rect(10, height: h * 2, width: 3)";
+                let lexer = Lexer::new(&input);
+                let mut parser = Parser::new(lexer);
+                let program = parse_without_errors(&mut parser);
+                assert_eq!(format!("{}", program.statements[0]), "rect(10, height: h * 2, width: 3);");
+
+                let tests = vec![
+                    ("f(x: )", "1:6: invalid token: )"),
+                    ("f(1 + x: 2)", "1:8: expected RPAREN but got :"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let errors = parser.parse_program().1;
+                    assert_eq!(format!("{}: {}", errors[0].span, errors[0]), *expected);
+                    }
+                }
+            }
@@ -1,10 +1,16 @@
 extern crate rustyline;
 
+use std::fs;
+use std::process;
+
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
 use crate::{lexer, parser, ast, errors, evaluator, object};
 
+// the name which is used instead of a file name when errors are reported in the console.
+const REPL_NAME: &str = "<repl>";
+
 pub fn start() {
     let mut rl = Editor::<()>::new();
     // if environment is defined outside loop,
@@ -31,9 +37,17 @@ pub fn start() {
 
                 let lexer = lexer::Lexer::new(&line);
                 let mut parser = parser::Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
-                let evaluated = environment.evaluate(&program);
-                println!("{}", evaluated.unwrap());
+                let (program, errors) = parser.parse_program();
+                if !errors.is_empty() {
+                    for err in errors.iter() {
+                        println!("{}", err.report(REPL_NAME));
+                    }
+                    continue;
+                }
+                match environment.evaluate(&program) {
+                    Err(err) => println!("{}", err.report(REPL_NAME)),
+                    Ok(evaluated) => println!("{}", evaluated),
+                }
             },
             Err(ReadlineError::Interrupted) => {
                 println!("ctrl-c");
@@ -50,3 +64,28 @@ pub fn start() {
         }
     }
 }
+
+// evaluate a script file, and exit with the position of the error if it fails.
+pub fn execute(path: &str) {
+    let input = match fs::read_to_string(path) {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
+        }
+    };
+    let lexer = lexer::Lexer::new(&input);
+    let mut parser = parser::Parser::new(lexer);
+    let (program, errors) = parser.parse_program();
+    if !errors.is_empty() {
+        for err in errors.iter() {
+            eprintln!("{}", err.report(path));
+        }
+        process::exit(1);
+    }
+    let mut environment = evaluator::Environment::new();
+    if let Err(err) = environment.evaluate(&program) {
+        eprintln!("{}", err.report(path));
+        process::exit(1);
+    }
+}
@@ -1,28 +1,51 @@
+use std::fmt;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
 use super::ast::{Precedence};
 
 #[derive(Debug,Eq,PartialEq, Clone, Copy, Hash, Ord, PartialOrd)]
 pub enum TokenKind {
-     ILLEGAL,     // ILLEGAL
      EOF,         // EOF
 
     // identifier and literal
      IDENT,       // IDENT
      INT,         // 123...
+     FLOAT,       // 1.23, 1e9...
 
     // operator
      ASSIGN,      // =
+     PlusAssign,     // +=
+     MinusAssign,    // -=
+     AsteriskAssign, // *=
+     SlashAssign,    // /=
+     PercentAssign,  // %=
      PLUS,        // +
      MINUS,       // -
      BANG,        // !
      ASTERISK,    // *
      SLASH,       // /
+     PERCENT,     // %
+     POWER,       // **
+     AMPERSAND,   // &
+     PIPE,        // |
+     CARET,       // ^
+     TILDE,       // ~
+     ShiftLeft,   // <<
+     ShiftRight,  // >>
      LT,          // <
      GT,          // >
      EQ,          // ==
      NotEq,      // !=
+     LtEq,        // <=
+     GtEq,        // >=
+     AND,         // &&
+     OR,          // ||
+     FatArrow,    // =>
 
      // delimiter
      COMMA,       // ,
+     ELLIPSIS,    // ...
      SEMICOLON,   // ;
 
      LPAREN,      // (
@@ -38,8 +61,23 @@ pub enum TokenKind {
      IF,          // if
      ELSE,        // else
      RETURN,      // return
+     WHILE,       // while
+     FOR,         // for
+     IN,          // in
+     BREAK,       // break
+     CONTINUE,    // continue
+     MATCH,       // match
+     THROW,       // throw
+     TRY,         // try
+     CATCH,       // catch
+     FINALLY,     // finally
 
      STRING,
+     TemplateHead,   // "...${
+     TemplateMiddle, // }...${
+     TemplateTail,   // }..."
+
+     COMMENT,     // # ..., // ... or /* ... */
 
      LBRACKET,    // [
      RBRACKET,    // ]
@@ -74,30 +112,136 @@ pub fn get_keyword(ident: &str) -> TokenKind {
         "return" => {
             TokenKind::RETURN
         }
+        "while" => {
+            TokenKind::WHILE
+        }
+        "for" => {
+            TokenKind::FOR
+        }
+        "in" => {
+            TokenKind::IN
+        }
+        "break" => {
+            TokenKind::BREAK
+        }
+        "continue" => {
+            TokenKind::CONTINUE
+        }
+        "match" => {
+            TokenKind::MATCH
+        }
+        "throw" => {
+            TokenKind::THROW
+        }
+        "try" => {
+            TokenKind::TRY
+        }
+        "catch" => {
+            TokenKind::CATCH
+        }
+        "finally" => {
+            TokenKind::FINALLY
+        }
         _ => {
             TokenKind::IDENT
         }
     }
 }
 
+// the position of a token or a node in the source code.
+// line and column start from 1, and start and end are byte offsets of the input.
+// a span whose line is 0 means that the position is unknown.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, start: usize, end: usize) -> Self {
+        Span{line, column, start, end}
+    }
+
+    // the span which starts at self and ends at other.
+    pub fn to(self, other: Span) -> Span {
+        Span{line: self.line, column: self.column, start: self.start, end: other.end}
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        self.line == 0
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// spans are ignored when tokens, nodes or errors are compared,
+// so that the same code written in different places is regarded as equal.
+impl PartialEq for Span {
+    fn eq(&self, _: &Span) -> bool {
+        true
+    }
+}
+
+impl Eq for Span {}
+
+impl PartialOrd for Span {
+    fn partial_cmp(&self, other: &Span) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Span {
+    fn cmp(&self, _: &Span) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl Hash for Span {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
 // if other module refers to Token, pub is needed to write 
 #[derive(Debug, Clone, PartialEq, Hash,Eq,Ord, PartialOrd)]
 pub struct Token {
     pub token_type: TokenKind,
-    pub literal: String
+    pub literal: String,
+    pub span: Span,
 }
 
 impl Token {
     pub fn get_precedence(&mut self) -> Precedence {
         match self.token_type {
+            TokenKind::ASSIGN => Precedence::ASSIGN,
+            TokenKind::PlusAssign => Precedence::ASSIGN,
+            TokenKind::MinusAssign => Precedence::ASSIGN,
+            TokenKind::AsteriskAssign => Precedence::ASSIGN,
+            TokenKind::SlashAssign => Precedence::ASSIGN,
+            TokenKind::PercentAssign => Precedence::ASSIGN,
             TokenKind::EQ => Precedence::EQUALS,
             TokenKind::NotEq => Precedence::EQUALS,
             TokenKind::LT => Precedence::LESSGREATER,        
             TokenKind::GT => Precedence::LESSGREATER,        
+            TokenKind::LtEq => Precedence::LESSGREATER,
+            TokenKind::GtEq => Precedence::LESSGREATER,
+            TokenKind::AND => Precedence::LOGICALAND,
+            TokenKind::OR => Precedence::LOGICALOR,
+            TokenKind::PIPE => Precedence::BITOR,
+            TokenKind::CARET => Precedence::BITXOR,
+            TokenKind::AMPERSAND => Precedence::BITAND,
+            TokenKind::ShiftLeft => Precedence::SHIFT,
+            TokenKind::ShiftRight => Precedence::SHIFT,
             TokenKind::PLUS => Precedence::SUM,
             TokenKind::MINUS => Precedence::SUM,
             TokenKind::SLASH => Precedence::PRODUCT,
             TokenKind::ASTERISK => Precedence::PRODUCT,
+            TokenKind::PERCENT => Precedence::PRODUCT,
+            TokenKind::POWER => Precedence::EXPONENT,
             TokenKind::LPAREN => Precedence::CALL,
             TokenKind::LBRACKET => Precedence::LBRACKET,
             _                   => Precedence::LOWEST